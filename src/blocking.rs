@@ -0,0 +1,149 @@
+//! Blinking without an async runtime.
+use embassy_time::{Duration, Instant};
+use embedded_hal::delay::DelayNs;
+
+use crate::{Blinker, BlinkerError, Config, Output, StepOutcome, TimeSource};
+
+/// A blocking counterpart of [`Blinker`] for firmware without an executor(e.g. bootloaders).
+/// It runs the same [`Schedule`](crate::Schedule) stack with a blocking `embedded_hal::delay::DelayNs`,
+/// through `step_blocking` and `run_blocking`.
+/// The async functions(e.g. `Blinker::step`) are not available, since waiting would block the executor.
+/// ```ignore
+/// let mut blinker = BlockingBlinker::<_, 1, _>::with_delay(led_pin, delay);
+/// blinker.push_schedule(Schedule::Blinks(3, Duration::from_millis(100), Duration::from_millis(100))).unwrap();
+/// blinker.run_blocking().unwrap();
+/// ```
+pub type BlockingBlinker<P, const N: usize, D> = Blinker<P, N, BlockingDelayClock<D>>;
+
+impl<P: Output, const N: usize, D: DelayNs> BlockingBlinker<P, N, D> {
+    /// Create a new `BlockingBlinker` struct
+    pub fn with_delay(pin: P, delay: D) -> Self {
+        Self::with_clock(pin, Config::default(), BlockingDelayClock::new(delay))
+    }
+    /// Executes one step of the schedule that is on the top of the stack, blocking until it is over,
+    /// and returns what it did(see [`StepOutcome`]).
+    /// If there is no schedule, does nothing regardless of `Config::idle`.
+    /// Returns an error if the pin is in a bad state(check if your environment supports "infallible" GPIO operations),
+    /// unless `Config::errors` is `ErrorPolicy::Ignore`.
+    pub fn step_blocking(&mut self) -> Result<StepOutcome, BlinkerError<P::Error>> {
+        if let Some(deadline) = self.begin_step(self.clock.now())? {
+            self.clock.wait_until_blocking(deadline);
+        }
+        self.finish_step()
+    }
+    /// Runs schedules until the stack becomes empty.
+    /// Returns immediately if there is no schedule, and never returns while an infinite schedule is running.
+    /// Returns an error if the pin is in a bad state, unless `Config::errors` is `ErrorPolicy::Ignore`.
+    pub fn run_blocking(&mut self) -> Result<(), BlinkerError<P::Error>> {
+        while !self.timeline.is_empty() {
+            self.step_blocking()?;
+        }
+        Ok(())
+    }
+}
+
+/// A source of time backed by a blocking `embedded_hal::delay::DelayNs`, used by [`BlockingBlinker`].
+///
+/// Like [`DelayClock`](crate::DelayClock), this clock counts the time it has waited for.
+/// It implements [`TimeSource`] but not the async [`Clock`](crate::Clock), since waiting through it would block the executor.
+pub struct BlockingDelayClock<D: DelayNs> {
+    delay: D,
+    now: Instant,
+}
+
+impl<D: DelayNs> BlockingDelayClock<D> {
+    /// Create a new `BlockingDelayClock` struct
+    pub fn new(delay: D) -> Self {
+        Self {
+            delay,
+            now: Instant::from_ticks(0),
+        }
+    }
+    /// Blocks until `deadline`.
+    /// Returns immediately if `deadline` has already passed.
+    pub fn wait_until_blocking(&mut self, deadline: Instant) {
+        while self.now < deadline {
+            let remaining = (deadline - self.now).as_micros();
+            match u32::try_from(remaining) {
+                Ok(us) => {
+                    self.delay.delay_us(us);
+                    self.now = deadline;
+                }
+                Err(_) => {
+                    self.delay.delay_us(u32::MAX);
+                    self.now += Duration::from_micros(u32::MAX.into());
+                }
+            }
+        }
+    }
+}
+
+impl<D: DelayNs> TimeSource for BlockingDelayClock<D> {
+    fn now(&self) -> Instant {
+        self.now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Schedule;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction};
+
+    /// Records delays without waiting.
+    struct RecordingDelay<'a>(&'a mut std::vec::Vec<u32>);
+
+    impl DelayNs for RecordingDelay<'_> {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0.push(ns / 1000);
+        }
+        fn delay_us(&mut self, us: u32) {
+            self.0.push(us);
+        }
+    }
+
+    #[test]
+    fn test_blocking_run() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut delays = std::vec::Vec::new();
+        let mut blinker =
+            BlockingBlinker::<_, 2, _>::with_delay(&mut pin, RecordingDelay(&mut delays));
+
+        let _ = blinker.push_schedule(Schedule::Blinks(
+            2,
+            Duration::from_millis(10),
+            Duration::from_millis(30),
+        ));
+        blinker.run_blocking().expect("infallible");
+
+        assert!(blinker.is_idle());
+        drop(blinker);
+        assert_eq!(delays, [10_000, 30_000, 10_000, 30_000]);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blocking_step() {
+        let expectations = [Transaction::toggle(), Transaction::toggle()];
+        let mut pin = PinMock::new(&expectations);
+        let mut delays = std::vec::Vec::new();
+        let mut blinker =
+            BlockingBlinker::<_, 2, _>::with_delay(&mut pin, RecordingDelay(&mut delays));
+
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(20)));
+        blinker.step_blocking().expect("infallible");
+        blinker.step_blocking().expect("infallible");
+
+        // 無限スケジュールは残っているはず
+        assert_eq!(blinker.depth(), 1);
+        drop(blinker);
+        assert_eq!(delays, [20_000, 20_000]);
+        pin.done();
+    }
+}
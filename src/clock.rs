@@ -0,0 +1,159 @@
+//! Sources of time for a [`Blinker`](crate::Blinker).
+use embassy_time::{Duration, Instant};
+use embedded_hal_async::delay::DelayNs;
+
+/// Source of the current time of a [`Blinker`](crate::Blinker),
+/// used to compute deadlines(e.g. `Blinker::push_for`, `Blinker::time_until_next_transition`).
+///
+/// Instants and durations are expressed with `embassy_time` types,
+/// which does not require an embassy time driver as long as [`EmbassyClock`] is not used.
+pub trait TimeSource {
+    /// Returns the current instant.
+    fn now(&self) -> Instant;
+}
+
+/// Source of time of a [`Blinker`](crate::Blinker) that can wait asynchronously, so that it can run on any async runtime.
+/// It is required by the async functions of the blinker(e.g. `Blinker::step`, `Blinker::run`).
+pub trait Clock: TimeSource {
+    /// Waits until `deadline`.
+    /// Returns immediately if `deadline` has already passed.
+    #[allow(async_fn_in_trait)]
+    async fn wait_until(&mut self, deadline: Instant);
+}
+
+/// A [`Clock`] backed by `embassy_time::Timer`, used by [`Blinker`](crate::Blinker) by default.
+/// Implements [`TimeSource`] and [`Clock`] only with the `embassy` feature(enabled by default), and requires an embassy time driver.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmbassyClock;
+
+#[cfg(feature = "embassy")]
+impl TimeSource for EmbassyClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+#[cfg(feature = "embassy")]
+impl Clock for EmbassyClock {
+    async fn wait_until(&mut self, deadline: Instant) {
+        embassy_time::Timer::at(deadline).await
+    }
+}
+
+/// A [`Clock`] backed by any `embedded_hal_async::delay::DelayNs`(e.g. of RTIC monotonics or a HAL).
+///
+/// Since a delay cannot tell the current time, this clock counts the time it has waited for.
+/// Time spent between calls of `Blinker::step` is not counted, so `Timing::Absolute` cannot catch up with it.
+/// Waits are done in slices of `DelayClock::SLICE`, each counted as soon as it is over,
+/// so when a wait is interrupted(e.g. by `select`), only the slice in progress is lost and the next wait resumes from there.
+pub struct DelayClock<D: DelayNs> {
+    delay: D,
+    now: Instant,
+}
+
+impl<D: DelayNs> DelayClock<D> {
+    /// The longest delay a wait is made of.
+    pub const SLICE: Duration = Duration::from_millis(1);
+
+    /// Create a new `DelayClock` struct
+    pub fn new(delay: D) -> Self {
+        Self {
+            delay,
+            now: Instant::from_ticks(0),
+        }
+    }
+}
+
+impl<D: DelayNs> TimeSource for DelayClock<D> {
+    fn now(&self) -> Instant {
+        self.now
+    }
+}
+
+impl<D: DelayNs> Clock for DelayClock<D> {
+    async fn wait_until(&mut self, deadline: Instant) {
+        while self.now < deadline {
+            let slice = (deadline - self.now).min(Self::SLICE);
+            self.delay.delay_us(slice.as_micros() as u32).await;
+            self.now += slice;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Blinker, Config, Schedule};
+    use embassy_futures::select::select;
+    use embassy_futures::{block_on, yield_now};
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, Transaction};
+
+    /// Records delays without waiting.
+    struct RecordingDelay<'a>(&'a mut std::vec::Vec<u32>);
+
+    impl DelayNs for RecordingDelay<'_> {
+        async fn delay_ns(&mut self, ns: u32) {
+            self.0.push(ns / 1000);
+        }
+        async fn delay_us(&mut self, us: u32) {
+            self.0.push(us);
+        }
+    }
+
+    #[test]
+    fn test_delay_clock() {
+        let expectations = [Transaction::toggle(), Transaction::toggle()];
+        let mut pin = PinMock::new(&expectations);
+        let mut delays = std::vec::Vec::new();
+        let clock = DelayClock::new(RecordingDelay(&mut delays));
+        let mut blinker = Blinker::<_, 2, _>::with_clock(&mut pin, Config::default(), clock);
+
+        let _ = blinker.push_schedule(Schedule::Finite(1, Duration::from_millis(30)));
+        block_on(blinker.run_until_idle()).expect("infallible");
+
+        drop(blinker);
+        assert_eq!(delays, [1000; 60]);
+        pin.done();
+    }
+
+    /// Records delays, yielding once before each of them is over.
+    struct YieldingDelay<'a>(&'a mut std::vec::Vec<u32>);
+
+    impl DelayNs for YieldingDelay<'_> {
+        async fn delay_ns(&mut self, ns: u32) {
+            self.delay_us(ns / 1000).await
+        }
+        async fn delay_us(&mut self, us: u32) {
+            yield_now().await;
+            self.0.push(us);
+        }
+    }
+
+    #[test]
+    fn test_delay_clock_cancelled() {
+        let expectations = [Transaction::toggle()];
+        let mut pin = PinMock::new(&expectations);
+        let mut delays = std::vec::Vec::new();
+        let clock = DelayClock::new(YieldingDelay(&mut delays));
+        let mut blinker = Blinker::<_, 2, _>::with_clock(&mut pin, Config::default(), clock);
+
+        let _ = blinker.push_schedule(Schedule::Finite(0, Duration::from_millis(30)));
+        block_on(async {
+            // ステップの途中で待ちを中断する
+            let cancel = async {
+                for _ in 0..5 {
+                    yield_now().await;
+                }
+            };
+            let _ = select(blinker.step(), cancel).await;
+            assert_eq!(blinker.clock.now(), Instant::from_millis(5));
+            assert_eq!(blinker.next_deadline(), Some(Instant::from_millis(30)));
+            blinker.step().await.expect("infallible");
+        });
+
+        // 中断までに待った分は数えられ、ステップ全体で30msだけ待つはず
+        drop(blinker);
+        assert_eq!(delays.iter().sum::<u32>(), 30_000);
+        pin.done();
+    }
+}
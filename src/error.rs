@@ -0,0 +1,74 @@
+//! Errors of a [`Blinker`](crate::Blinker).
+use core::fmt;
+
+use embedded_hal::digital::{Error, ErrorKind};
+
+use crate::Schedule;
+
+/// An error returned by a [`Blinker`](crate::Blinker).
+/// `E` is the error type of the pin.
+#[derive(Debug)]
+pub enum BlinkerError<E> {
+    /// The stack is full. The schedule that could not be pushed is given back.
+    StackFull(Schedule),
+    /// The schedule can never be executed(see `Schedule::is_valid`). It is given back.
+    InvalidSchedule(Schedule),
+    /// The pin is in a bad state(check if your environment supports "infallible" GPIO operations).
+    Pin(E),
+}
+
+impl<E> BlinkerError<E> {
+    /// Returns the schedule that was rejected, if any.
+    pub fn into_schedule(self) -> Option<Schedule> {
+        match self {
+            BlinkerError::StackFull(schedule) | BlinkerError::InvalidSchedule(schedule) => {
+                Some(schedule)
+            }
+            BlinkerError::Pin(_) => None,
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for BlinkerError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlinkerError::StackFull(_) => f.write_str("the schedule stack is full"),
+            BlinkerError::InvalidSchedule(_) => f.write_str(concat!(
+                "the schedule has a zero duration, an empty pattern, ",
+                "or a fade with no step or too short a period"
+            )),
+            BlinkerError::Pin(e) => write!(f, "pin error: {e:?}"),
+        }
+    }
+}
+
+impl<E: fmt::Debug> core::error::Error for BlinkerError<E> {}
+
+/// Errors of the pin keep their kind, so that a `BlinkerError` can be handled like any other GPIO error.
+impl<E: Error> Error for BlinkerError<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            BlinkerError::Pin(e) => e.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embassy_time::Duration;
+
+    #[test]
+    fn test_blinker_error_kind() {
+        let schedule = Schedule::Infinite(Duration::from_millis(10));
+        let error = BlinkerError::<ErrorKind>::StackFull(schedule.clone());
+        assert_eq!(error.kind(), ErrorKind::Other);
+        assert_eq!(error.into_schedule(), Some(schedule));
+
+        // ピンのエラーはそのまま伝わるはず
+        let error = BlinkerError::Pin(ErrorKind::Other);
+        assert_eq!(error.kind(), ErrorKind::Other);
+        assert!(error.into_schedule().is_none());
+    }
+}
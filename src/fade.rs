@@ -0,0 +1,299 @@
+//! Smooth brightness ramps used by `Schedule::Breathe`, `Schedule::FadeIn` and `Schedule::FadeOut`.
+use embassy_time::Duration;
+
+use crate::timeline::Drive;
+use crate::Level;
+
+/// A brightness ramp between two levels, played by `Schedule::Breathe`, `Schedule::FadeIn` and `Schedule::FadeOut`.
+///
+/// The ramp is made of `steps` equal steps, each driving the output at a fixed level,
+/// so it is smooth on a [`PwmOutput`](crate::PwmOutput) and a plain blink on a digital pin.
+/// `min` and `max` are perceptual brightnesses from 0 to 255: they are eased, then corrected by `gamma` into 16-bit levels.
+/// ```ignore
+/// let fade = Fade {
+///     easing: Easing::EaseInOut,
+///     ..Fade::new(Duration::from_secs(4), 0, 255)
+/// };
+/// blinker.push_schedule(Schedule::Breathe(fade))?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fade {
+    /// How long the ramp takes: a whole breath(up and down) for `Schedule::Breathe`,
+    /// or the whole fade for `Schedule::FadeIn` and `Schedule::FadeOut`.
+    pub period: Duration,
+    /// Brightness at the bottom of the ramp.
+    pub min: u8,
+    /// Brightness at the top of the ramp.
+    pub max: u8,
+    /// How the brightness moves from `min` to `max`.
+    pub easing: Easing,
+    /// How the brightness is mapped to the level of the output.
+    pub gamma: Gamma,
+    /// Number of steps it takes to go from `min` to `max`.
+    pub steps: u8,
+}
+
+impl Fade {
+    /// Number of steps a ramp is made of by default.
+    pub const DEFAULT_STEPS: u8 = 32;
+
+    /// Create a new `Fade` with linear easing, `Gamma::Gamma22` and `Fade::DEFAULT_STEPS` steps.
+    pub const fn new(period: Duration, min: u8, max: u8) -> Self {
+        Self {
+            period,
+            min,
+            max,
+            easing: Easing::Linear,
+            gamma: Gamma::Gamma22,
+            steps: Self::DEFAULT_STEPS,
+        }
+    }
+    /// Returns the level of the output at `progress`, from 0(`min`) to 255(`max`).
+    pub fn level(&self, progress: u8) -> Level {
+        let eased = i32::from(self.easing.apply(progress));
+        let (min, max) = (i32::from(self.min), i32::from(self.max));
+        let brightness = min + (max - min) * eased / 255;
+        // `brightness` is between `min` and `max`
+        Level(self.gamma.apply(brightness as u8))
+    }
+
+    /// Step `index` of `Schedule::FadeIn`.
+    pub(crate) fn fade_in_step(&self, index: usize) -> (Drive, Duration) {
+        let steps = usize::from(self.steps);
+        (
+            Drive::Set(self.level(progress(index + 1, steps))),
+            self.step_duration(index, steps),
+        )
+    }
+    /// Step `index` of `Schedule::FadeOut`.
+    pub(crate) fn fade_out_step(&self, index: usize) -> (Drive, Duration) {
+        let steps = usize::from(self.steps);
+        (
+            Drive::Set(self.level(u8::MAX - progress(index + 1, steps))),
+            self.step_duration(index, steps),
+        )
+    }
+    /// Step `index` of `Schedule::Breathe`: up from `min` to `max`, then down.
+    pub(crate) fn breathe_step(&self, index: usize) -> (Drive, Duration) {
+        let steps = usize::from(self.steps);
+        let position = if index <= steps {
+            index
+        } else {
+            2 * steps - index
+        };
+        (
+            Drive::Set(self.level(progress(position, steps))),
+            self.step_duration(index, 2 * steps),
+        )
+    }
+    /// How long the steps of `Schedule::FadeIn` or `Schedule::FadeOut` from `index` on last in total.
+    pub(crate) fn remaining_duration(&self, index: usize) -> Duration {
+        let elapsed = self.offset(index, usize::from(self.steps));
+        Duration::from_ticks(self.period.as_ticks() - elapsed)
+    }
+    /// Duration of step `index` when the period is split into `count` steps.
+    /// The durations add up to the period exactly.
+    fn step_duration(&self, index: usize, count: usize) -> Duration {
+        Duration::from_ticks(self.offset(index + 1, count) - self.offset(index, count))
+    }
+    /// Ticks from the beginning of the period to the beginning of step `index`, when it is split into `count` steps.
+    fn offset(&self, index: usize, count: usize) -> u64 {
+        let ticks = u128::from(self.period.as_ticks());
+        // at most the period, since `index` is at most `count`
+        (ticks * index as u128 / count as u128) as u64
+    }
+}
+
+/// Returns `position / count` scaled to 0..=255.
+fn progress(position: usize, count: usize) -> u8 {
+    // `position` is at most `count`
+    (position * 255 / count) as u8
+}
+
+/// How the brightness of a [`Fade`] moves from `min` to `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    /// At a constant rate.
+    #[default]
+    Linear,
+    /// Slowly at first, then faster(quadratic).
+    EaseIn,
+    /// Fast at first, then slower(quadratic).
+    EaseOut,
+    /// Slowly at both ends(quadratic).
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps `progress`(from 0 to 255) to eased progress(from 0 to 255).
+    pub fn apply(self, progress: u8) -> u8 {
+        let x = u32::from(progress);
+        let eased = match self {
+            Easing::Linear => x,
+            Easing::EaseIn => x * x / 255,
+            Easing::EaseOut => 255 - (255 - x) * (255 - x) / 255,
+            Easing::EaseInOut if x < 128 => 2 * x * x / 255,
+            Easing::EaseInOut => 255 - 2 * (255 - x) * (255 - x) / 255,
+        };
+        // `eased` is at most 255
+        eased as u8
+    }
+}
+
+/// How the brightness of a [`Fade`] is mapped to the level of the output.
+///
+/// LEDs look much brighter than their duty cycle at low levels,
+/// so a linear ramp of the duty cycle seems to jump at the bottom and to stall at the top.
+/// Gamma correction makes the ramp look even.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Gamma {
+    /// No correction: the level is the brightness.
+    Linear,
+    /// Gamma 2.2, close to how the eye perceives brightness.
+    #[default]
+    Gamma22,
+    /// Gamma 2.8, stronger correction commonly used for LEDs.
+    Gamma28,
+}
+
+impl Gamma {
+    /// Maps `brightness`(from 0 to 255) to the level of the output(from 0 to 65535, see [`Level`]).
+    /// The level has more precision than the brightness, so that dim brightnesses are not rounded down to zero.
+    pub fn apply(self, brightness: u8) -> u16 {
+        match self {
+            Gamma::Linear => Level::from_brightness(brightness).0,
+            Gamma::Gamma22 => GAMMA_22[usize::from(brightness)],
+            Gamma::Gamma28 => GAMMA_28[usize::from(brightness)],
+        }
+    }
+}
+
+/// `round((i / 255) ^ 2.2 * 65535)`
+#[rustfmt::skip]
+const GAMMA_22: [u16; 256] = [
+    0, 0, 2, 4, 7, 11, 17, 24, 32, 42, 53, 65, 79, 94, 111, 129,
+    148, 169, 192, 216, 242, 270, 299, 330, 362, 396, 432, 469, 508, 549, 591, 635,
+    681, 729, 779, 830, 883, 938, 995, 1053, 1113, 1175, 1239, 1305, 1373, 1443, 1514, 1587,
+    1663, 1740, 1819, 1900, 1983, 2068, 2155, 2243, 2334, 2427, 2521, 2618, 2717, 2817, 2920, 3024,
+    3131, 3240, 3350, 3463, 3578, 3694, 3813, 3934, 4057, 4182, 4309, 4438, 4570, 4703, 4838, 4976,
+    5115, 5257, 5401, 5547, 5695, 5845, 5998, 6152, 6309, 6468, 6629, 6792, 6957, 7124, 7294, 7466,
+    7640, 7816, 7994, 8175, 8358, 8543, 8730, 8919, 9111, 9305, 9501, 9699, 9900, 10102, 10307, 10515,
+    10724, 10936, 11150, 11366, 11585, 11806, 12029, 12254, 12482, 12712, 12944, 13179, 13416, 13655, 13896, 14140,
+    14386, 14635, 14885, 15138, 15394, 15652, 15912, 16174, 16439, 16706, 16975, 17247, 17521, 17798, 18077, 18358,
+    18642, 18928, 19216, 19507, 19800, 20095, 20393, 20694, 20996, 21301, 21609, 21919, 22231, 22546, 22863, 23182,
+    23504, 23829, 24156, 24485, 24817, 25151, 25487, 25826, 26168, 26512, 26858, 27207, 27558, 27912, 28268, 28627,
+    28988, 29351, 29717, 30086, 30457, 30830, 31206, 31585, 31966, 32349, 32735, 33124, 33514, 33908, 34304, 34702,
+    35103, 35507, 35913, 36321, 36732, 37146, 37562, 37981, 38402, 38825, 39252, 39680, 40112, 40546, 40982, 41421,
+    41862, 42306, 42753, 43202, 43654, 44108, 44565, 45025, 45487, 45951, 46418, 46888, 47360, 47835, 48313, 48793,
+    49275, 49761, 50249, 50739, 51232, 51728, 52226, 52727, 53230, 53736, 54245, 54756, 55270, 55787, 56306, 56828,
+    57352, 57879, 58409, 58941, 59476, 60014, 60554, 61097, 61642, 62190, 62741, 63295, 63851, 64410, 64971, 65535,
+];
+
+/// `round((i / 255) ^ 2.8 * 65535)`
+#[rustfmt::skip]
+const GAMMA_28: [u16; 256] = [
+    0, 0, 0, 0, 1, 1, 2, 3, 4, 6, 8, 10, 13, 16, 19, 24,
+    28, 33, 39, 46, 53, 60, 69, 78, 88, 98, 110, 122, 135, 149, 164, 179,
+    196, 214, 232, 252, 273, 295, 317, 341, 366, 393, 420, 449, 478, 510, 542, 575,
+    610, 647, 684, 723, 764, 806, 849, 894, 940, 988, 1037, 1088, 1140, 1194, 1250, 1307,
+    1366, 1427, 1489, 1553, 1619, 1686, 1756, 1827, 1900, 1975, 2051, 2130, 2210, 2293, 2377, 2463,
+    2552, 2642, 2734, 2829, 2925, 3024, 3124, 3227, 3332, 3439, 3548, 3660, 3774, 3890, 4008, 4128,
+    4251, 4376, 4504, 4634, 4766, 4901, 5038, 5177, 5319, 5464, 5611, 5760, 5912, 6067, 6224, 6384,
+    6546, 6711, 6879, 7049, 7222, 7397, 7576, 7757, 7941, 8128, 8317, 8509, 8704, 8902, 9103, 9307,
+    9514, 9723, 9936, 10151, 10370, 10591, 10816, 11043, 11274, 11507, 11744, 11984, 12227, 12473, 12722, 12975,
+    13230, 13489, 13751, 14017, 14285, 14557, 14833, 15111, 15393, 15678, 15967, 16259, 16554, 16853, 17155, 17461,
+    17770, 18083, 18399, 18719, 19042, 19369, 19700, 20034, 20372, 20713, 21058, 21407, 21759, 22115, 22475, 22838,
+    23206, 23577, 23952, 24330, 24713, 25099, 25489, 25884, 26282, 26683, 27089, 27499, 27913, 28330, 28752, 29178,
+    29608, 30041, 30479, 30921, 31367, 31818, 32272, 32730, 33193, 33660, 34131, 34606, 35085, 35569, 36057, 36549,
+    37046, 37547, 38052, 38561, 39075, 39593, 40116, 40643, 41175, 41711, 42251, 42796, 43346, 43899, 44458, 45021,
+    45588, 46161, 46737, 47319, 47905, 48495, 49091, 49691, 50295, 50905, 51519, 52138, 52761, 53390, 54023, 54661,
+    55303, 55951, 56604, 57261, 57923, 58590, 59262, 59939, 60621, 61308, 62000, 62697, 63399, 64106, 64818, 65535,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Schedule, Timeline};
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_easing_and_gamma() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            // 端点は変わらず、単調に増えるはず
+            assert_eq!(easing.apply(0), 0);
+            assert_eq!(easing.apply(255), 255);
+            assert!((0..255).all(|x| easing.apply(x) <= easing.apply(x + 1)));
+        }
+        assert!(Easing::EaseIn.apply(64) < 64);
+        assert!(Easing::EaseOut.apply(64) > 64);
+
+        for gamma in [Gamma::Gamma22, Gamma::Gamma28] {
+            assert_eq!(gamma.apply(0), 0);
+            assert_eq!(gamma.apply(255), u16::MAX);
+            assert!((0..255).all(|x| gamma.apply(x) <= gamma.apply(x + 1)));
+            // 中間の明るさは暗めに補正されるはず
+            assert!(gamma.apply(128) < Level::from_brightness(64).0);
+        }
+        // 暗い明るさも0に丸められないはず
+        assert!((2..=255).all(|x| Gamma::Gamma22.apply(x) > 0));
+        assert_eq!(Gamma::Linear.apply(128), Level::from_brightness(128).0);
+    }
+
+    #[test]
+    fn test_fade_steps() {
+        let fade = Fade {
+            gamma: Gamma::Linear,
+            steps: 4,
+            ..Fade::new(ms(40), 0, 255)
+        };
+        let mut timeline = Timeline::<2>::new();
+        let _ = timeline.push_schedule(Schedule::FadeIn(fade));
+        let _ = timeline.push_schedule(Schedule::FadeOut(fade));
+
+        let edges: std::vec::Vec<_> = timeline
+            .clone()
+            .map(|edge| (edge.level, edge.duration))
+            .collect();
+        // 上に積んだフェードアウトの後にフェードインが続くはず
+        let expected = [192, 128, 64, 0, 63, 127, 191, 255]
+            .map(|brightness| (Level::from_brightness(brightness), ms(10)));
+        assert_eq!(edges, expected);
+        assert_eq!(timeline.total_duration(), Some(ms(80)));
+        assert_eq!(timeline.remaining_count(), Some(1));
+    }
+
+    #[test]
+    fn test_breathe() {
+        let fade = Fade {
+            easing: Easing::EaseInOut,
+            gamma: Gamma::Linear,
+            steps: 2,
+            ..Fade::new(ms(100), 10, 210)
+        };
+        let schedule = Schedule::Breathe(fade);
+        assert!(schedule.is_valid());
+        let mut timeline = Timeline::<1>::new();
+        let _ = timeline.push_schedule(schedule);
+
+        let levels: std::vec::Vec<_> = timeline.by_ref().take(8).map(|edge| edge.level).collect();
+        // 最小と最大の間を往復し続けるはず
+        let expected = [10, 108, 210, 108, 10, 108, 210, 108].map(Level::from_brightness);
+        assert_eq!(levels, expected);
+        assert_eq!(timeline.len(), 1);
+        assert_eq!(timeline.total_duration(), None);
+
+        // ステップ数が0のものや、1ティックに満たないステップがあるものは実行できないはず
+        let fade = Fade { steps: 0, ..fade };
+        assert!(!Schedule::Breathe(fade).is_valid());
+        let fade = Fade::new(Duration::from_ticks(3), 0, 255);
+        assert!(!Schedule::FadeIn(fade).is_valid());
+    }
+}
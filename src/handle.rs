@@ -0,0 +1,281 @@
+//! Controlling a [`Blinker`] from other tasks.
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::channel::{Channel, TrySendError};
+
+use crate::{Blinker, BlinkerError, Clock, Output, Schedule, StepOutcome};
+
+/// A command sent from a [`BlinkerHandle`] to the [`Blinker`].
+#[derive(Debug)]
+pub enum Command {
+    /// Push a new schedule to the stack(see `Blinker::push_schedule`).
+    /// If the stack is full or the schedule is invalid, `Blinker::step_with` returns the error.
+    Push(Schedule),
+    /// Replace the schedule on the top of the stack, or push it if the stack is empty(see `Blinker::replace_top`).
+    /// If the schedule is invalid, `Blinker::step_with` returns the error.
+    Replace(Schedule),
+    /// Assert a named condition with a priority and a schedule(see `Blinker::assert_condition`).
+    /// If the stack is full or the schedule is invalid, `Blinker::step_with` returns the error.
+    Assert(&'static str, u8, Schedule),
+    /// Deassert a named condition(see `Blinker::deassert_condition`).
+    Deassert(&'static str),
+    /// Clear schedules and turn the output off(see `Blinker::reset`).
+    Clear,
+}
+
+/// A queue of commands shared between the task running a [`Blinker`] and the tasks controlling it.
+/// `M` is the mutex used to share the queue, `Q` is the number of commands that can be queued.
+///
+/// Usually placed in a `static`, and handed out to other tasks through [`BlinkerControl::handle`].
+/// ```ignore
+/// static CONTROL: BlinkerControl<CriticalSectionRawMutex, 4> = BlinkerControl::new();
+///
+/// #[embassy_executor::task]
+/// async fn blink_task(led_pin: Output<'static>) {
+///     let mut blinker = Blinker::<_, 2>::new(led_pin);
+///     loop {
+///         // rejected schedules are reported as errors, and ignored here
+///         let _ = blinker.step_with(&CONTROL).await;
+///     }
+/// }
+///
+/// #[embassy_executor::task]
+/// async fn button_task(handle: BlinkerHandle<'static, CriticalSectionRawMutex, 4>) {
+///     loop {
+///         wait_for_button().await;
+///         handle.push_schedule(Schedule::Finite(5, Duration::from_millis(100))).await;
+///     }
+/// }
+/// ```
+pub struct BlinkerControl<M: RawMutex, const Q: usize> {
+    commands: Channel<M, Command, Q>,
+}
+
+impl<M: RawMutex, const Q: usize> BlinkerControl<M, Q> {
+    /// Create a new `BlinkerControl` struct
+    pub const fn new() -> Self {
+        Self {
+            commands: Channel::new(),
+        }
+    }
+    /// Returns a handle that sends commands to the blinker running with this control.
+    pub fn handle(&self) -> BlinkerHandle<'_, M, Q> {
+        BlinkerHandle { control: self }
+    }
+}
+
+impl<M: RawMutex, const Q: usize> Default for BlinkerControl<M, Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sends commands to a [`Blinker`] running in another task.
+/// Handles are cheap to copy, so each task can have its own.
+pub struct BlinkerHandle<'a, M: RawMutex, const Q: usize> {
+    control: &'a BlinkerControl<M, Q>,
+}
+
+impl<M: RawMutex, const Q: usize> Clone for BlinkerHandle<'_, M, Q> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M: RawMutex, const Q: usize> Copy for BlinkerHandle<'_, M, Q> {}
+
+impl<M: RawMutex, const Q: usize> BlinkerHandle<'_, M, Q> {
+    /// Sends a command, waiting until there is room in the queue.
+    pub async fn send(&self, command: Command) {
+        self.control.commands.send(command).await
+    }
+    /// Sends a command.
+    /// Returns an error if the queue is full.
+    pub fn try_send(&self, command: Command) -> Result<(), Command> {
+        self.control
+            .commands
+            .try_send(command)
+            .map_err(|TrySendError::Full(command)| command)
+    }
+    /// Push a new schedule to the stack of the blinker.
+    pub async fn push_schedule(&self, schedule: Schedule) {
+        self.send(Command::Push(schedule)).await
+    }
+    /// Replace the schedule on the top of the stack of the blinker.
+    pub async fn replace_schedule(&self, schedule: Schedule) {
+        self.send(Command::Replace(schedule)).await
+    }
+    /// Assert a named condition of the blinker.
+    pub async fn assert_condition(&self, name: &'static str, priority: u8, schedule: Schedule) {
+        self.send(Command::Assert(name, priority, schedule)).await
+    }
+    /// Deassert a named condition of the blinker.
+    pub async fn deassert_condition(&self, name: &'static str) {
+        self.send(Command::Deassert(name)).await
+    }
+    /// Clear schedules of the blinker and turn the output off.
+    pub async fn clear(&self) {
+        self.send(Command::Clear).await
+    }
+}
+
+impl<P: Output, const N: usize, C: Clock> Blinker<P, N, C> {
+    /// Executes one step like `Blinker::step`, while applying the commands sent through `control`.
+    /// When a command arrives in the middle of the step, the step is interrupted(see `Blinker::step`)
+    /// and this function returns right after applying the command, so that the change takes effect immediately.
+    /// If there is no schedule, waits for a command regardless of `Config::idle`.
+    /// Returns what the step did like `Blinker::step`, or `StepOutcome::Interrupted` if a command was applied while waiting.
+    /// Returns an error if the pin is in a bad state(check if your environment supports "infallible" GPIO operations).
+    ///
+    /// Returns `BlinkerError::StackFull` or `BlinkerError::InvalidSchedule` if a command could not be applied,
+    /// giving the schedule back(see `BlinkerError::into_schedule`).
+    /// The commands left in the queue are applied on the next call.
+    pub async fn step_with<M: RawMutex, const Q: usize>(
+        &mut self,
+        control: &BlinkerControl<M, Q>,
+    ) -> Result<StepOutcome, BlinkerError<P::Error>> {
+        while let Ok(command) = control.commands.try_receive() {
+            self.apply(command)?;
+        }
+        if self.timeline.is_empty() {
+            let command = control.commands.receive().await;
+            self.apply(command)?;
+            return Ok(StepOutcome::Interrupted);
+        }
+        match select(self.step(), control.commands.receive()).await {
+            Either::First(result) => result,
+            Either::Second(command) => {
+                self.apply(command)?;
+                Ok(StepOutcome::Interrupted)
+            }
+        }
+    }
+
+    fn apply(&mut self, command: Command) -> Result<(), BlinkerError<P::Error>> {
+        match command {
+            Command::Push(schedule) => {
+                self.push_schedule(schedule)?;
+            }
+            Command::Replace(schedule) => {
+                self.replace_top(schedule)?;
+            }
+            Command::Assert(name, priority, schedule) => {
+                self.assert_condition(name, priority, schedule)?;
+            }
+            Command::Deassert(name) => {
+                let _ = self.deassert_condition(name);
+            }
+            Command::Clear => self.reset()?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embassy_futures::block_on;
+    use embassy_futures::join::join;
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embassy_time::{Duration, Instant, Timer};
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction};
+
+    #[test]
+    fn test_handle_replace_interrupts_step() {
+        let expectations = [
+            Transaction::toggle(),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+        let control = BlinkerControl::<NoopRawMutex, 2>::new();
+        let handle = control.handle();
+
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_secs(10)));
+
+        block_on(join(
+            async {
+                let start = Instant::now();
+                blinker.step_with(&control).await.expect("infallible");
+                // 10秒待たずにすぐ反応するはず
+                assert!(start.elapsed() < Duration::from_secs(1));
+                blinker.step_with(&control).await.expect("infallible");
+                blinker.step_with(&control).await.expect("infallible");
+                assert!(blinker.is_idle());
+            },
+            async {
+                Timer::after(Duration::from_millis(20)).await;
+                handle
+                    .replace_schedule(Schedule::Blinks(
+                        1,
+                        Duration::from_millis(10),
+                        Duration::from_millis(10),
+                    ))
+                    .await;
+            },
+        ));
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_handle_reports_rejected_schedule() {
+        let mut pin = PinMock::new(&[]);
+        let mut blinker = Blinker::<_, 1>::new(&mut pin);
+        let control = BlinkerControl::<NoopRawMutex, 2>::new();
+        let handle = control.handle();
+        let schedule = Schedule::Infinite(Duration::from_millis(10));
+
+        blinker.push_schedule(schedule.clone()).unwrap();
+        let invalid = Schedule::Infinite(Duration::from_ticks(0));
+        assert!(handle.try_send(Command::Push(invalid)).is_ok());
+        assert!(handle.try_send(Command::Push(schedule.clone())).is_ok());
+
+        block_on(async {
+            // 捨てずにエラーとして返すはず
+            assert!(matches!(
+                blinker.step_with(&control).await,
+                Err(BlinkerError::InvalidSchedule(_))
+            ));
+            let full = blinker.step_with(&control).await.unwrap_err();
+            assert!(matches!(full, BlinkerError::StackFull(_)));
+            assert_eq!(full.into_schedule(), Some(schedule));
+        });
+
+        assert_eq!(blinker.depth(), 1);
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_handle_wakes_idle_blinker() {
+        let expectations = [Transaction::toggle(), Transaction::set(State::Low)];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+        let control = BlinkerControl::<NoopRawMutex, 2>::new();
+        let handle = control.handle();
+
+        block_on(join(
+            async {
+                // スケジュールが来るまで待つはず
+                blinker.step_with(&control).await.expect("infallible");
+                assert_eq!(blinker.depth(), 1);
+                blinker.step_with(&control).await.expect("infallible");
+                assert!(blinker.is_idle());
+            },
+            async {
+                Timer::after(Duration::from_millis(20)).await;
+                handle
+                    .push_schedule(Schedule::Infinite(Duration::from_secs(10)))
+                    .await;
+                Timer::after(Duration::from_millis(20)).await;
+                handle.clear().await;
+            },
+        ));
+
+        drop(blinker);
+        pin.done();
+    }
+}
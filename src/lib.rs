@@ -7,22 +7,40 @@
 //! - async/await support
 //! - Configurable blink patterns through [`Schedule`]
 //! - Support for both finite and infinite blinking sequences
+//! - Asymmetric on/off durations (e.g. short flashes with long gaps)
+//! - Multi-segment patterns such as "double flash, pause" or heartbeat
+//! - Optional drift-free timing based on absolute deadlines
+//! - Control from other tasks through a shareable handle (backed by embassy-sync)
+//! - Works with plain `OutputPin`s too, tracking the level in software
+//! - Runs on any async runtime(embassy-time by default, or any `embedded_hal_async::delay::DelayNs`)
+//! - Blocking counterpart([`BlockingBlinker`]) for firmware without an executor
+//! - Poll-driven API(`Blinker::poll`) for superloops and timer interrupts
+//! - Hardware-free [`Timeline`] engine to test, measure and preview schedules
+//! - Stack of overlays or FIFO queue of notifications, with configurable overflow policies
+//! - Priority arbitration of named conditions, falling back automatically when one is cleared
+//! - Time-bounded schedules that revert after a given duration
+//! - PWM brightness levels over `embedded_hal::pwm::SetDutyCycle`, with global dimming
+//! - Breathing and fade schedules with easing curves and gamma correction
 //! - No heap allocation (uses [heapless::Vec](https://docs.rs/heapless/latest/heapless/struct.Vec.html))
 //!
+//! # Cargo features
+//! - `embassy`(default): provides [`EmbassyClock`], the default source of time of [`Blinker`] backed by `embassy_time::Timer`.
+//!   Disable it to use the crate without an embassy time driver, with [`DelayClock`] or your own [`Clock`].
+//!   `embassy-time` itself is always a dependency, since its `Duration` and `Instant` are used throughout the API,
+//!   but they do not need a time driver.
+//!
 //! The main purpose of this library is to provide a simple and efficient way to control an led to create blinking patterns,
 //! but it can also be used for any purpose that requires toggling an output pin according to specific patterns.
 //!
 //! # Example
 //! ## blinks with 500ms interval
 //! ```ignore
-//! async fn blink_task(led_pin: impl StatefulOutputPin) {
+//! async fn blink_task<P: StatefulOutputPin>(led_pin: P) -> Result<Infallible, BlinkerError<P::Error>> {
 //!     let mut blinker = Blinker::<_, 1>::new(led_pin);
 //!     // Blink with 500ms interval
-//!     let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(500)));
+//!     blinker.push_schedule(Schedule::Infinite(Duration::from_millis(500)))?;
 //!     // Run the blink pattern
-//!     loop {
-//!         let _ = blinker.step().await;
-//!     }
+//!     blinker.run().await
 //! }
 //! ```
 //! ## blinks faster when a button is pushed
@@ -30,95 +48,930 @@
 //! async fn blink_task(led_pin: impl StatefulOutputPin, rx: Receiver<Event>) {
 //!     let mut blinker = Blinker::<_, 2>::new(led_pin);
 //!     // Blink with 500ms interval
-//!     let _ = blinker.push_schedule(Schedule::Ininite(Duration::from_millis(500)));
+//!     let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(500)));
 //!     // Run the blink pattern
+//!     // `step` is cancellation safe, so it can be raced against other futures.
 //!     loop {
-//!         if let Either::Second(Event::ButtonPushed) = select(blinker.step().await, rx.recv()).await {
+//!         if let Either::Second(Event::ButtonPushed) = select(blinker.step(), rx.receive()).await {
 //!             // ignore overflow
-//!             let _ = blinker.push_schedule(Schedule::Ininite(Duration::from_millis(100)));
+//!             let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(100)));
 //!         }
 //!     }
 //! }
 //! ```
+//! ## controlling the blinker from other tasks
+//! see [`BlinkerControl`] and [`BlinkerHandle`].
+//! ## double flash, then pause
+//! ```ignore
+//! async fn blink_task<P: StatefulOutputPin>(led_pin: P) -> Result<Infallible, BlinkerError<P::Error>> {
+//!     let mut blinker = Blinker::<_, 1>::new(led_pin);
+//!     const DOUBLE_FLASH: Pattern = &[
+//!         Segment::high(Duration::from_millis(100)),
+//!         Segment::low(Duration::from_millis(100)),
+//!         Segment::high(Duration::from_millis(100)),
+//!         Segment::low(Duration::from_millis(700)),
+//!     ];
+//!     blinker.push_schedule(Schedule::InfinitePattern(DOUBLE_FLASH))?;
+//!     blinker.run().await
+//! }
+//! ```
 #![cfg_attr(not(test), no_std)]
 
-use embassy_time::{Duration, Timer};
-use embedded_hal::digital::StatefulOutputPin;
-use heapless::Vec;
+mod blocking;
+mod clock;
+mod error;
+mod fade;
+mod handle;
+mod output;
+mod pwm;
+mod timeline;
+mod tracked;
+
+pub use blocking::{BlockingBlinker, BlockingDelayClock};
+pub use clock::{Clock, DelayClock, EmbassyClock, TimeSource};
+pub use error::BlinkerError;
+pub use fade::{Easing, Fade, Gamma};
+pub use handle::{BlinkerControl, BlinkerHandle, Command};
+pub use output::Output;
+pub use pwm::PwmOutput;
+pub use timeline::{Edge, ScheduleId, Timeline};
+pub use tracked::TrackedPin;
+
+use core::convert::Infallible;
+use embassy_time::{Duration, Instant};
+use embedded_hal::digital::PinState;
+use timeline::Drive;
 
 /// controls an output pin to create blinking patterns.
-pub struct Blinker<P: StatefulOutputPin, const N: usize> {
+///
+/// The schedules are stepped by a [`Timeline`]; the blinker drives the pin and waits for each step.
+///
+/// `C` is the source of time(see [`Clock`], or [`TimeSource`] for blinkers that are not run asynchronously).
+/// It defaults to [`EmbassyClock`], which requires the `embassy` feature.
+pub struct Blinker<P: Output, const N: usize, C = EmbassyClock> {
     pin: P,
-    schedule: Vec<Schedule, N>,
+    clock: C,
+    timeline: Timeline<N>,
+    config: Config,
+    /// Deadline of the step in flight, if its future was dropped before completion.
+    deadline: Option<Instant>,
+    /// Deadline of the last completed step, used as the start of the next one with `Timing::Absolute`.
+    last_deadline: Option<Instant>,
 }
 
-impl<P: StatefulOutputPin, const N: usize> Blinker<P, N> {
+#[cfg(feature = "embassy")]
+impl<P: Output, const N: usize> Blinker<P, N> {
     /// Create a new `Blinker` struct
     pub fn new(pin: P) -> Self {
+        Self::with_config(pin, Config::default())
+    }
+    /// Create a new `Blinker` struct with the given configuration
+    pub fn with_config(pin: P, config: Config) -> Self {
+        Self::with_clock(pin, config, EmbassyClock)
+    }
+}
+
+impl<P: Output, const N: usize> Blinker<P, N, ()> {
+    /// Create a new `Blinker` struct without a source of time, to be driven by `Blinker::poll`
+    /// (use `Blinker::with_clock(pin, config, ())` to give it a configuration).
+    pub fn new_polled(pin: P) -> Self {
+        Self::with_clock(pin, Config::default(), ())
+    }
+}
+
+impl<P: Output, const N: usize, C> Blinker<P, N, C> {
+    /// Create a new `Blinker` struct with the given configuration and source of time
+    pub fn with_clock(pin: P, config: Config, clock: C) -> Self {
         Self {
             pin,
-            schedule: Vec::new(),
+            clock,
+            timeline: Timeline::new(),
+            config,
+            deadline: None,
+            last_deadline: None,
+        }
+    }
+    /// Push a new schedule to the stack, and returns its identifier(see `Blinker::remove`).
+    /// Returns an error if the stack is full, or if the schedule is invalid(see `Schedule::is_valid`).
+    ///
+    /// If a step of the previous schedule was interrupted, it is abandoned so that the new schedule starts on the next step.
+    /// The interrupted step is executed again from the start when the previous schedule resumes.
+    /// An abandoned toggle resumes from the level before it, so the pin is toggled only once in total.
+    ///
+    /// The level of the pin is recorded for the previous schedule, and restored when the new one is popped,
+    /// so that the previous schedule resumes as if it had never been interrupted.
+    /// If the level is not known yet, it is read(`StatefulOutputPin::is_set_high`) at the beginning of the next step.
+    ///
+    /// If the stack is full, room is made according to `Config::overflow`.
+    /// With `Order::Queue`, the schedule is queued instead(see [`Order`]), and the running one is not interrupted
+    /// unless the new schedule is executed first.
+    /// The schedule has the lowest priority(see `Blinker::push_with_priority`).
+    pub fn push_schedule(
+        &mut self,
+        schedule: Schedule,
+    ) -> Result<ScheduleId, BlinkerError<P::Error>> {
+        self.push_with_priority(schedule, 0)
+    }
+    /// Push a new schedule to the stack with the given priority, in the same way as `Blinker::push_schedule`.
+    /// The priority only decides which schedule is dropped with `Overflow::DropLowerPriority`;
+    /// the schedule on the top of the stack is executed regardless of its priority.
+    pub fn push_with_priority(
+        &mut self,
+        schedule: Schedule,
+        priority: u8,
+    ) -> Result<ScheduleId, BlinkerError<P::Error>> {
+        if !schedule.is_valid() {
+            return Err(BlinkerError::InvalidSchedule(schedule));
+        }
+        let top = self.top_id();
+        let overflow = self.config.overflow;
+        let id = match self.config.order {
+            Order::Stack => self
+                .timeline
+                .push_with_priority(schedule, priority, overflow),
+            Order::Queue => self.timeline.enqueue(schedule, priority, overflow),
+        }
+        .map_err(BlinkerError::StackFull)?;
+        self.restart_unless_top(top);
+        Ok(id)
+    }
+    /// Push a new schedule to the stack in the same way as `Blinker::push_schedule`,
+    /// and removes it at `expires_at` even if it is in the middle of a step or a pattern.
+    /// `expires_at` is measured by the same time base as the one the blinker is driven with(see `Blinker::push_for`).
+    ///
+    /// When it expires while it is running, the step in flight is cut short at that instant,
+    /// and the schedule below resumes at the level it was interrupted at.
+    /// When it expires while it is interrupted by another schedule, it is removed silently.
+    pub fn push_until(
+        &mut self,
+        schedule: Schedule,
+        expires_at: Instant,
+    ) -> Result<ScheduleId, BlinkerError<P::Error>> {
+        let id = self.push_schedule(schedule)?;
+        self.timeline.set_expiry(id, expires_at);
+        Ok(id)
+    }
+    /// Asserts a named condition with a priority and the schedule that displays it, and returns the identifier of the schedule.
+    /// The blinker always displays the asserted condition with the highest priority,
+    /// and falls back to the next one when it is deasserted(see `Timeline::assert_condition` for the details).
+    /// Asserting a condition again replaces its schedule and priority, or does nothing if neither of them changes.
+    /// Returns an error if the stack is full(room is made according to `Config::overflow`, except `Overflow::ReplaceTop`),
+    /// or if the schedule is invalid(see `Schedule::is_valid`).
+    /// ```ignore
+    /// blinker.assert_condition("low battery", 1, Schedule::InfiniteOnOff(Duration::from_millis(100), Duration::from_secs(2)))?;
+    /// blinker.assert_condition("fault", 9, Schedule::Infinite(Duration::from_millis(100)))?;
+    /// // displays "fault"
+    /// blinker.deassert_condition("fault");
+    /// // displays "low battery" again
+    /// ```
+    pub fn assert_condition(
+        &mut self,
+        name: &'static str,
+        priority: u8,
+        schedule: Schedule,
+    ) -> Result<ScheduleId, BlinkerError<P::Error>> {
+        if !schedule.is_valid() {
+            return Err(BlinkerError::InvalidSchedule(schedule));
+        }
+        let top = self.top_id();
+        let id = self
+            .timeline
+            .assert_condition(name, priority, schedule, self.config.overflow)
+            .map_err(BlinkerError::StackFull)?;
+        self.restart_unless_top(top);
+        Ok(id)
+    }
+    /// Deasserts a named condition, removing its schedule from the stack, and returns the schedule.
+    /// Returns `None` if the condition is not asserted.
+    pub fn deassert_condition(&mut self, name: &'static str) -> Option<Schedule> {
+        let top = self.top_id();
+        let schedule = self.timeline.deassert_condition(name)?;
+        self.restart_unless_top(top);
+        Some(schedule)
+    }
+    /// Returns `true` if the named condition is asserted.
+    pub fn is_asserted(&self, name: &'static str) -> bool {
+        self.timeline.is_asserted(name)
+    }
+    /// Replaces the schedule on the top of the stack, or pushes it if the stack is empty.
+    /// Returns the identifier of the new schedule; the identifier of the replaced one is no longer valid.
+    /// If the replaced schedule displays a condition, the condition is deasserted.
+    /// The new schedule does not expire, even if the replaced one was pushed with `Blinker::push_until`.
+    /// Returns an error if the schedule is invalid(see `Schedule::is_valid`).
+    ///
+    /// The step in flight is abandoned, and the new schedule starts from its beginning on the next step.
+    pub fn replace_top(
+        &mut self,
+        schedule: Schedule,
+    ) -> Result<ScheduleId, BlinkerError<P::Error>> {
+        if !schedule.is_valid() {
+            return Err(BlinkerError::InvalidSchedule(schedule));
+        }
+        let id = self
+            .timeline
+            .replace_top(schedule)
+            .map_err(BlinkerError::StackFull)?;
+        self.restart();
+        Ok(id)
+    }
+    /// Pops the schedule on the top of the stack, and returns it.
+    ///
+    /// The step in flight is abandoned, and the level of the pin recorded for the schedule below
+    /// is restored at the beginning of the next step, before that schedule resumes.
+    pub fn pop_schedule(&mut self) -> Option<Schedule> {
+        let schedule = self.timeline.pop_schedule()?;
+        self.restart();
+        Some(schedule)
+    }
+    /// Returns `true` if the schedule with the given identifier is on the stack,
+    /// i.e. it is neither over nor removed.
+    /// Useful to find out when a schedule is over with `Blinker::poll`(see also [`StepOutcome`]).
+    pub fn contains(&self, id: ScheduleId) -> bool {
+        self.timeline.contains(id)
+    }
+    /// Returns the schedule on the top of the stack and its identifier, without removing it.
+    pub fn peek(&self) -> Option<(ScheduleId, &Schedule)> {
+        self.timeline.peek()
+    }
+    /// Removes the schedule with the given identifier from anywhere in the stack, and returns it.
+    /// Returns `None` if the schedule is not on the stack anymore(e.g. it is over, or it was replaced).
+    ///
+    /// Removing the schedule on the top is the same as `Blinker::pop_schedule`.
+    /// Removing one from the middle does not affect the running schedule.
+    pub fn remove(&mut self, id: ScheduleId) -> Option<Schedule> {
+        let top = self.top_id();
+        let schedule = self.timeline.remove(id)?;
+        self.restart_unless_top(top);
+        Some(schedule)
+    }
+    /// Returns the identifier of the schedule on the top of the stack.
+    fn top_id(&self) -> Option<ScheduleId> {
+        self.timeline.peek().map(|(id, _)| id)
+    }
+    /// Restarts unless the schedule on the top of the stack is still `top`.
+    fn restart_unless_top(&mut self, top: Option<ScheduleId>) {
+        if self.top_id() != top {
+            self.restart();
         }
     }
-    /// Push a new schedule to the stack
-    /// Returns an error if the stack is full
-    pub fn push_schedule(&mut self, schedule: Schedule) -> Result<(), Schedule> {
-        self.schedule.push(schedule)
+    /// Abandons the step in flight, and restarts the timeline of `Timing::Absolute`.
+    fn restart(&mut self) {
+        self.deadline = None;
+        self.last_deadline = None;
+        self.timeline.toggling = false;
+    }
+    /// Returns the number of schedules on the stack.
+    pub fn depth(&self) -> usize {
+        self.timeline.len()
+    }
+    /// Returns `true` if there is no schedule to execute.
+    pub fn is_idle(&self) -> bool {
+        self.timeline.is_empty()
+    }
+    /// Returns the schedule being executed, i.e. the one on the top of the stack.
+    pub fn active_schedule(&self) -> Option<&Schedule> {
+        self.timeline.active_schedule()
+    }
+    /// Returns how many more times the active schedule repeats before it is popped(see `Timeline::remaining_count`).
+    pub fn remaining_count(&self) -> Option<u32> {
+        self.timeline.remaining_count()
     }
-    /// Clears schedules and sets the pin to low.
+    /// Returns the logical level of the pin(see [`Polarity`]), or `None` if it has not been driven or read yet,
+    /// or if it failed.
+    pub fn level(&self) -> Option<Level> {
+        self.timeline.level()
+    }
+    /// Returns the output driven by this blinker, e.g. to dim a [`PwmOutput`].
+    /// Driving it directly confuses the blinker, which keeps track of its level.
+    pub fn output_mut(&mut self) -> &mut P {
+        &mut self.pin
+    }
+    /// Returns the instant the step in flight is over, i.e. when the pin is driven next.
+    /// Returns `None` if no step is in flight.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+    /// Returns the timeline stepping the schedules of this blinker.
+    /// The step in flight, if any, is still part of it, since it is counted as done only when its deadline has passed.
+    /// Use `Blinker::preview` to look at what is left to be done.
+    pub fn timeline(&self) -> &Timeline<N> {
+        &self.timeline
+    }
+    /// Returns a copy of the timeline that starts where the step in flight ends,
+    /// i.e. the edges the blinker produces from `Blinker::next_deadline` on.
+    /// ```ignore
+    /// let left = blinker.preview().total_duration();
+    /// for edge in blinker.preview() {
+    ///     draw(edge.level, edge.duration);
+    /// }
+    /// ```
+    pub fn preview(&self) -> Timeline<N> {
+        let mut preview = self.timeline.clone();
+        if self.deadline.is_some() {
+            preview.toggling = false;
+            if let Some((_, Some(level))) = preview.advance() {
+                preview.level = Some(level);
+            }
+        }
+        preview
+    }
+    /// Clears schedules and turns the output off(sets the pin to low, or high if `Config::polarity` is `Polarity::ActiveLow`).
     /// Returns an error if the pin is in a bad state(check if your environment supports "infallible" GPIO operations)
-    pub fn reset(&mut self) -> Result<(), P::Error> {
-        self.pin.set_low()?;
-        self.schedule.clear();
+    pub fn reset(&mut self) -> Result<(), BlinkerError<P::Error>> {
+        self.pin
+            .set_level(Level::OFF, self.config.polarity)
+            .map_err(BlinkerError::Pin)?;
+        self.timeline.clear();
+        self.timeline.set_level(Level::OFF);
+        self.restart();
         Ok(())
     }
-    /// Executes one step of the schedule that is on the top of the stack.
-    /// If there is no schedule, does nothing(so be careful if you call this function in a loop).
-    /// Returns an error if the pin is in a bad state(check if your environment supports "infallible" GPIO operations).
-    pub async fn step(&mut self) -> Result<(), P::Error> {
-        if let Some(schedule) = self.schedule.last() {
-            match schedule {
-                Schedule::Finite(_, dur) | Schedule::Infinite(dur) => {
-                    self.pin.toggle()?;
-                    Timer::after(*dur).await;
+    /// Drives the schedules without waiting, for superloops and timer interrupts.
+    /// `now` is the current time, measured by whatever time base the caller has.
+    ///
+    /// If the step in flight is not over yet, does nothing.
+    /// Otherwise completes it, drives the pin for the next step, and returns the deadline of that step:
+    /// call `poll` again at(or after) that deadline.
+    /// Returns `None` if there is nothing to do until a schedule is pushed.
+    /// Only one step that drives the pin is executed per call, so if the returned deadline has already passed, call `poll` again right away.
+    ///
+    /// Schedules behave in the same way as with `Blinker::step`, and both can be mixed.
+    /// ```ignore
+    /// let mut blinker = Blinker::<_, 2, _>::new_polled(led_pin);
+    /// let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(500)));
+    /// loop {
+    ///     let now = Instant::from_micros(timer.now_micros());
+    ///     if let Ok(Some(deadline)) = blinker.poll(now) {
+    ///         timer.set_alarm(deadline.as_micros());
+    ///     }
+    ///     do_other_work();
+    /// }
+    /// ```
+    /// Returns an error if the pin is in a bad state(check if your environment supports "infallible" GPIO operations),
+    /// unless `Config::errors` is `ErrorPolicy::Ignore`.
+    pub fn poll(&mut self, now: Instant) -> Result<Option<Instant>, BlinkerError<P::Error>> {
+        if let Some(deadline) = self.deadline {
+            if now < deadline {
+                return Ok(Some(deadline));
+            }
+            self.finish_step()?;
+        }
+        loop {
+            if let Some(deadline) = self.begin_step(now)? {
+                return Ok(Some(deadline));
+            }
+            // schedules that do not drive the pin(e.g. `Schedule::Blinks` with a count of zero) are skipped
+            if self.finish_step()? == StepOutcome::Idle {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Starts a step of the schedule on the top of the stack, unless one is already in flight.
+    /// Returns the deadline of the step in flight, or `None` if there is nothing to wait for.
+    fn begin_step(&mut self, now: Instant) -> Result<Option<Instant>, BlinkerError<P::Error>> {
+        let top = self.top_id();
+        if self.timeline.expire(now) {
+            self.restart_unless_top(top);
+        }
+        if self.timeline.capture_pending {
+            self.capture_level()?;
+        }
+        if let Some(level) = self.timeline.restore {
+            self.restore(level)?;
+            self.timeline.restore = None;
+        }
+        if self.deadline.is_none() {
+            match self.start_step()? {
+                Some(dur) => {
+                    let deadline = self.next_start(now, dur) + dur;
+                    self.deadline = Some(match self.timeline.top_expiry() {
+                        Some(expires_at) => deadline.min(expires_at),
+                        None => deadline,
+                    });
                 }
+                None => self.last_deadline = None,
             }
         }
-        self.decrease_count();
+        Ok(self.deadline)
+    }
+
+    /// Completes the step in flight once its deadline has passed, and counts it.
+    fn finish_step(&mut self) -> Result<StepOutcome, BlinkerError<P::Error>> {
+        self.timeline.toggling = false;
+        let deadline = self.deadline.take();
+        if deadline.is_some() {
+            self.last_deadline = deadline;
+        }
+        match self.decrease_count()? {
+            Some(id) => Ok(StepOutcome::ScheduleCompleted(id)),
+            None if deadline.is_some() => Ok(StepOutcome::Stepped),
+            None => Ok(StepOutcome::Idle),
+        }
+    }
+
+    /// Reads the level of the pin and records it for the interrupted schedules that are waiting for it.
+    fn capture_level(&mut self) -> Result<(), BlinkerError<P::Error>> {
+        let level = match self.pin.level(self.config.polarity) {
+            Ok(level) => level,
+            Err(e) => return self.handle_error(e),
+        };
+        self.timeline.capture(level);
         Ok(())
     }
 
-    fn decrease_count(&mut self) {
-        let mut should_pop = false;
-        if let Some(Schedule::Finite(ref mut count, _)) = self.schedule.last_mut() {
-            if let Some(c) = count.checked_sub(1) {
-                *count = c;
-            } else {
-                should_pop = true;
+    /// Returns the instant a step lasting for `dur` and beginning at `now` starts at, according to `Config::timing`.
+    fn next_start(&self, now: Instant, dur: Duration) -> Instant {
+        match (self.config.timing, self.last_deadline) {
+            (Timing::Absolute(Lag::CatchUp), Some(last)) => last,
+            (Timing::Absolute(Lag::Skip), Some(last)) if last + dur > now => last,
+            _ => now,
+        }
+    }
+
+    /// Drives the pin for the step of the schedule that is on the top of the stack.
+    /// Returns how long the step lasts, or `None` if there is nothing to do.
+    fn start_step(&mut self) -> Result<Option<Duration>, BlinkerError<P::Error>> {
+        let Some((drive, dur)) = self.timeline.current_step() else {
+            return Ok(None);
+        };
+        self.drive(drive)?;
+        self.timeline.toggling = matches!(drive, Drive::Toggle);
+        Ok(Some(dur))
+    }
+
+    /// Drives the pin, keeping track of its level.
+    /// If the pin fails, its level is considered unknown.
+    fn drive(&mut self, drive: Drive) -> Result<(), BlinkerError<P::Error>> {
+        let result = match drive {
+            Drive::Toggle => self
+                .pin
+                .toggle(self.config.polarity)
+                .map(|()| self.timeline.level.map(Level::toggled)),
+            Drive::Set(level) => self
+                .pin
+                .set_level(level, self.config.polarity)
+                .map(|()| Some(level)),
+        };
+        match result {
+            Ok(level) => {
+                self.timeline.level = level;
+                Ok(())
+            }
+            Err(e) => {
+                self.timeline.level = None;
+                self.handle_error(e)
+            }
+        }
+    }
+
+    /// Handles a pin error according to `Config::errors`.
+    fn handle_error(&self, e: P::Error) -> Result<(), BlinkerError<P::Error>> {
+        match self.config.errors {
+            ErrorPolicy::Propagate => Err(BlinkerError::Pin(e)),
+            ErrorPolicy::Ignore => Ok(()),
+        }
+    }
+
+    /// Counts the step, and restores the level of the pin recorded for the schedule below if the top one is over.
+    /// Returns the identifier of the schedule that is over, if any.
+    fn decrease_count(&mut self) -> Result<Option<ScheduleId>, BlinkerError<P::Error>> {
+        let Some((id, restore)) = self.timeline.advance() else {
+            return Ok(None);
+        };
+        if let Some(level) = restore {
+            self.restore(level)?;
+        }
+        Ok(Some(id))
+    }
+
+    /// Drives the pin back to the level recorded for the schedule that resumes, if its next step toggles the pin.
+    /// Otherwise the next step drives the pin to its own level anyway, so the pin is left alone to avoid a spurious edge.
+    fn restore(&mut self, level: Level) -> Result<(), BlinkerError<P::Error>> {
+        match self.timeline.current_step() {
+            Some((Drive::Toggle, _)) => self.drive(Drive::Set(level)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<P: Output, const N: usize, C: TimeSource> Blinker<P, N, C> {
+    /// Push a new schedule to the stack in the same way as `Blinker::push_schedule`,
+    /// and removes it once `lifetime` has passed from now, according to the clock of the blinker(see `Blinker::push_until`).
+    /// If `lifetime` goes past the latest representable instant(e.g. `Duration::MAX`), the schedule never expires.
+    /// ```ignore
+    /// // blink fast for 10 seconds after a button press, then revert
+    /// blinker.push_for(Schedule::Infinite(Duration::from_millis(50)), Duration::from_secs(10))?;
+    /// ```
+    pub fn push_for(
+        &mut self,
+        schedule: Schedule,
+        lifetime: Duration,
+    ) -> Result<ScheduleId, BlinkerError<P::Error>> {
+        match self.clock.now().checked_add(lifetime) {
+            Some(expires_at) => self.push_until(schedule, expires_at),
+            None => self.push_schedule(schedule),
+        }
+    }
+
+    /// Returns how long it is until the pin is driven next, according to the clock of the blinker.
+    /// Returns `None` if no step is in flight.
+    pub fn time_until_next_transition(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(self.clock.now()))
+    }
+}
+
+impl<P: Output, const N: usize, C: Clock> Blinker<P, N, C> {
+    /// Executes one step of the schedule that is on the top of the stack.
+    /// If there is no schedule, does nothing(so be careful if you call this function in a loop),
+    /// unless `Config::idle` is `Idle::Wait`, in which case the returned future never completes,
+    /// so it has to be raced with a source of new schedules(see [`Idle`]).
+    /// Returns an error if the pin is in a bad state(check if your environment supports "infallible" GPIO operations),
+    /// unless `Config::errors` is `ErrorPolicy::Ignore`.
+    ///
+    /// Returns what the step did(see [`StepOutcome`]), e.g. to power down once a finite schedule is over:
+    /// ```ignore
+    /// let goodbye = blinker.push_schedule(Schedule::Blinks(3, Duration::from_millis(100), Duration::from_millis(100)))?;
+    /// while blinker.step().await? != StepOutcome::ScheduleCompleted(goodbye) {}
+    /// power_down();
+    /// ```
+    ///
+    /// This function is cancellation safe.
+    /// If the returned future is dropped before completion(e.g. by `select`), the next call does not drive the pin again,
+    /// but resumes waiting for the remaining time of the interrupted step and then counts it as done.
+    pub async fn step(&mut self) -> Result<StepOutcome, BlinkerError<P::Error>> {
+        if self.timeline.is_empty() && self.config.idle == Idle::Wait {
+            return core::future::pending().await;
+        }
+        if let Some(deadline) = self.begin_step(self.clock.now())? {
+            self.clock.wait_until(deadline).await;
+        }
+        self.finish_step()
+    }
+
+    /// Runs schedules forever.
+    /// While there is no schedule, waits without consuming CPU(regardless of `Config::idle`),
+    /// so the future has to be raced with a source of new schedules(e.g. in `select`) to ever run one after that.
+    /// Returns an error if the pin is in a bad state, unless `Config::errors` is `ErrorPolicy::Ignore`.
+    pub async fn run(&mut self) -> Result<Infallible, BlinkerError<P::Error>> {
+        loop {
+            if self.timeline.is_empty() {
+                core::future::pending::<()>().await;
             }
+            self.step().await?;
+        }
+    }
+
+    /// Runs schedules until the stack becomes empty.
+    /// Returns immediately if there is no schedule, and never returns while an infinite schedule is running.
+    /// Returns an error if the pin is in a bad state, unless `Config::errors` is `ErrorPolicy::Ignore`.
+    pub async fn run_until_idle(&mut self) -> Result<(), BlinkerError<P::Error>> {
+        while !self.timeline.is_empty() {
+            self.step().await?;
         }
-        if should_pop {
-            self.schedule.pop();
+        Ok(())
+    }
+}
+
+/// What a step of a [`Blinker`] did(see `Blinker::step`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// There was nothing to do.
+    Idle,
+    /// A step was executed, and the schedule goes on.
+    Stepped,
+    /// The schedule with the given identifier is over, and was popped.
+    /// Schedules removed in any other way(e.g. `Blinker::remove`, expiry or overflow) are not reported.
+    ScheduleCompleted(ScheduleId),
+    /// The step was interrupted by a command before it was over(see `Blinker::step_with`).
+    Interrupted,
+}
+
+/// Configuration of a [`Blinker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Config {
+    /// How the deadline of each step is computed.
+    pub timing: Timing,
+    /// What `Blinker::step` does when there is no schedule.
+    pub idle: Idle,
+    /// How pin errors are handled.
+    pub errors: ErrorPolicy,
+    /// How the output is wired.
+    pub polarity: Polarity,
+    /// What `Blinker::push_schedule` does when the stack is full.
+    pub overflow: Overflow,
+    /// Where `Blinker::push_schedule` puts new schedules.
+    pub order: Order,
+}
+
+/// Where a [`Blinker`] puts pushed schedules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    /// On the top of the stack, so a new schedule interrupts the running one, which resumes when it is over.
+    /// Suitable for overlays.
+    #[default]
+    Stack,
+    /// In a queue(see `Timeline::enqueue`), so finite schedules are executed one after another in the order they are pushed,
+    /// and the infinite one pushed last runs in the background once they are over.
+    /// Suitable for sequential notifications such as "blink 3 times, then 5 times, then resume".
+    Queue,
+}
+
+/// What a [`Blinker`] does when a schedule is pushed while the stack is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Reject the new schedule with `BlinkerError::StackFull`.
+    #[default]
+    Reject,
+    /// Drop the oldest schedule, i.e. the one at the bottom of the stack.
+    /// With `Order::Queue`, the finite schedule queued first is dropped instead, so that the background keeps running.
+    EvictOldest,
+    /// Replace the schedule on the top of the stack(see `Blinker::replace_top`).
+    ReplaceTop,
+    /// Drop the schedule with the lowest priority(the oldest one among equals),
+    /// if it is lower than the priority of the new schedule(see `Blinker::push_with_priority`).
+    /// Otherwise the new schedule is rejected.
+    DropLowerPriority,
+}
+
+/// How the output(e.g. an LED) is wired to the pin.
+///
+/// Levels in schedules are logical: `PinState::High`(or any [`Level`] above `Level::OFF`) means "on"
+/// and `PinState::Low` means "off", and they are mapped to electrical levels of the pin according to the polarity.
+/// Toggling schedules(`Schedule::Infinite`, `Schedule::Finite`) are not affected on digital pins.
+/// The duty cycle of a [`PwmOutput`] is inverted with `Polarity::ActiveLow`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Polarity {
+    /// The output is on when the pin is high.
+    #[default]
+    ActiveHigh,
+    /// The output is on when the pin is low.
+    ActiveLow,
+}
+
+impl Polarity {
+    /// Maps a logical level to the electrical one, or vice versa.
+    fn apply(self, level: PinState) -> PinState {
+        match self {
+            Polarity::ActiveHigh => level,
+            Polarity::ActiveLow => !level,
         }
     }
 }
 
+/// How a [`Blinker`] handles errors of the pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Return the error from `Blinker::step`, `Blinker::run` and so on.
+    /// The failed step is executed again on the next call.
+    #[default]
+    Propagate,
+    /// Ignore the error and carry on as if the pin had been driven, so timing and counting are not affected.
+    /// The blinker considers the level of the pin unknown until it is driven successfully again.
+    Ignore,
+}
+
+/// What `Blinker::step` does when there is no schedule on the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Idle {
+    /// Return immediately.
+    /// Calling `step` in a loop without any other future to wait for busy-loops until a schedule is pushed.
+    #[default]
+    Return,
+    /// Never complete while there is no schedule, without consuming CPU.
+    /// Nothing wakes the future up when a schedule is pushed: pushing requires `&mut Blinker`,
+    /// so the returned future has to be dropped first, e.g. by racing it with the source of new schedules in `select`.
+    /// A loop that only awaits `step` hangs forever once the stack is empty.
+    /// Use `Blinker::step_with` to wait for commands from other tasks instead.
+    /// ```ignore
+    /// loop {
+    ///     if let Either::Second(schedule) = select(blinker.step(), rx.receive()).await {
+    ///         let _ = blinker.push_schedule(schedule);
+    ///     }
+    /// }
+    /// ```
+    Wait,
+}
+
+/// How the deadline of each step is computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timing {
+    /// Each step lasts for its duration from the moment it starts.
+    /// Any latency between calls of `Blinker::step` accumulates, so long-running blinkers drift.
+    #[default]
+    Relative,
+    /// Each step starts at the deadline of the previous one(like `embassy_time::Ticker`),
+    /// so transitions stay phase-locked to the time the schedule started.
+    /// The timeline restarts when a schedule is pushed, when the blinker is reset, or when there is nothing to do.
+    Absolute(Lag),
+}
+
+/// What a blinker with `Timing::Absolute` does when it has fallen behind,
+/// i.e. when a step has already been over by the time it starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lag {
+    /// Execute the late steps back-to-back without waiting until the blinker has caught up.
+    /// The pin may change rapidly, but no step is lost.
+    #[default]
+    CatchUp,
+    /// Skip the missed time.
+    /// The late step lasts for its whole duration from now on, and the timeline is re-anchored at that point.
+    Skip,
+}
+
 /// A blinking schedule that can be pushed to the `Blinker`.
 /// This represents how you want to blink the pin.
 /// see `Blinker::push_schedule`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Schedule {
     /// Periodically toggle the pin.
     /// The duration is the time between toggles.
     Infinite(Duration),
     /// Periodically toggle the pin a specified number of times.
+    /// The count is decreased on every step and the schedule is popped when it would go below zero,
+    /// so the pin is toggled `count + 1` times and is left at whatever level the last toggle produced.
+    /// Use `Blinks` to count whole on/off blinks instead.
     Finite(u32, Duration),
+    /// Periodically drive the pin high and low.
+    /// The first duration is the time the pin stays high(on), the second one is the time it stays low(off).
+    InfiniteOnOff(Duration, Duration),
+    /// Periodically drive the pin high and low a specified number of times.
+    /// The count is decreased on every step in the same way as `Finite`.
+    /// The first duration is the time the pin stays high(on), the second one is the time it stays low(off).
+    FiniteOnOff(u32, Duration, Duration),
+    /// Repeatedly walk through the segments of the pattern, one segment per step.
+    InfinitePattern(Pattern),
+    /// Walk through the segments of the pattern, one segment per step, a specified number of times.
+    /// The count is decreased every time the whole pattern has been walked through, in the same way as `Finite`,
+    /// so the pattern is played `count + 1` times.
+    FinitePattern(u32, Pattern),
+    /// Blink a specified number of times.
+    /// Each blink drives the pin high for the first duration(on), then low for the second one(off).
+    /// The pin makes exactly `2 * count` transitions(`count` rising and `count` falling ones)
+    /// and is left low(off, see [`Polarity`]) when the schedule is popped after the last off-time.
+    /// A count of zero does not touch the pin.
+    Blinks(u32, Duration, Duration),
+    /// Repeatedly ramp the brightness up from `min` to `max` and back down(see [`Fade`]), one level per step.
+    Breathe(Fade),
+    /// Ramp the brightness up from `min` to `max` once(see [`Fade`]), one level per step.
+    /// The output is left at `max` when the schedule is popped.
+    FadeIn(Fade),
+    /// Ramp the brightness down from `max` to `min` once(see [`Fade`]), one level per step.
+    /// The output is left at `min` when the schedule is popped.
+    FadeOut(Fade),
+}
+
+impl Schedule {
+    /// Number of steps it takes to go through the schedule once.
+    fn cycle_len(&self) -> usize {
+        match self {
+            Schedule::Finite(..) | Schedule::Infinite(..) => 1,
+            Schedule::FiniteOnOff(..) | Schedule::InfiniteOnOff(..) | Schedule::Blinks(..) => 2,
+            Schedule::FinitePattern(_, pattern) | Schedule::InfinitePattern(pattern) => {
+                pattern.len()
+            }
+            Schedule::FadeIn(fade) | Schedule::FadeOut(fade) => usize::from(fade.steps),
+            Schedule::Breathe(fade) => 2 * usize::from(fade.steps),
+        }
+    }
+    /// Returns `false` if the schedule can never be executed by a [`Blinker`]:
+    /// if any of its durations is zero, if its pattern is empty,
+    /// or if its fade has no step or is too short to give every step at least one tick.
+    /// `Blinker::push_schedule` rejects such schedules with `BlinkerError::InvalidSchedule`.
+    pub fn is_valid(&self) -> bool {
+        let zero = Duration::from_ticks(0);
+        match self {
+            Schedule::Finite(_, dur) | Schedule::Infinite(dur) => *dur != zero,
+            Schedule::FiniteOnOff(_, on, off)
+            | Schedule::InfiniteOnOff(on, off)
+            | Schedule::Blinks(_, on, off) => *on != zero && *off != zero,
+            Schedule::FinitePattern(_, pattern) | Schedule::InfinitePattern(pattern) => {
+                !pattern.is_empty() && pattern.iter().all(|segment| segment.duration != zero)
+            }
+            Schedule::Breathe(fade) | Schedule::FadeIn(fade) | Schedule::FadeOut(fade) => {
+                fade.steps != 0 && fade.period.as_ticks() >= self.cycle_len() as u64
+            }
+        }
+    }
+    /// Whether the schedule never pops itself.
+    fn is_infinite(&self) -> bool {
+        matches!(
+            self,
+            Schedule::Infinite(..)
+                | Schedule::InfiniteOnOff(..)
+                | Schedule::InfinitePattern(..)
+                | Schedule::Breathe(..)
+        )
+    }
+}
+
+/// A sequence of segments used by `Schedule::InfinitePattern` and `Schedule::FinitePattern`.
+/// Patterns are borrowed rather than stored in the schedule, so that every schedule stays small:
+/// declare them as `const` or `static` items.
+pub type Pattern = &'static [Segment];
+
+/// A part of a [`Pattern`]: the level the pin is driven to, and how long it stays there.
+/// The level is logical(see [`Polarity`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    /// The level the pin is driven to.
+    pub level: Level,
+    /// How long the pin stays at `level`.
+    pub duration: Duration,
+}
+
+impl Segment {
+    /// Create a new `Segment`
+    pub const fn new(level: PinState, duration: Duration) -> Self {
+        let level = match level {
+            PinState::High => Level::ON,
+            PinState::Low => Level::OFF,
+        };
+        Self { level, duration }
+    }
+    /// Create a segment that drives the output at the given brightness, from 0(off) to 255(fully on).
+    /// Digital pins are on at any brightness above zero(see [`Level`]).
+    pub const fn dimmed(brightness: u8, duration: Duration) -> Self {
+        Self {
+            level: Level::from_brightness(brightness),
+            duration,
+        }
+    }
+    /// Create a segment that drives the pin high(on, see [`Polarity`])
+    pub const fn high(duration: Duration) -> Self {
+        Self::new(PinState::High, duration)
+    }
+    /// Create a segment that drives the pin low(off, see [`Polarity`])
+    pub const fn low(duration: Duration) -> Self {
+        Self::new(PinState::Low, duration)
+    }
+}
+
+/// Logical level of an output: how bright it is, from `Level::OFF` to `Level::ON`(see [`Polarity`]).
+///
+/// Digital pins are on(high) at any level above `Level::OFF`,
+/// while the duty cycle of a [`PwmOutput`] follows the level.
+/// Levels have 16 bits, so that dim levels keep their precision on PWM channels with a fine resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Level(pub u16);
+
+impl Level {
+    /// The output is off.
+    pub const OFF: Level = Level(0);
+    /// The output is fully on.
+    pub const ON: Level = Level(u16::MAX);
+
+    /// Returns the level of the given brightness, from 0(off) to 255(fully on).
+    pub const fn from_brightness(brightness: u8) -> Self {
+        Level(brightness as u16 * 257)
+    }
+
+    /// Returns `true` if the output is on at this level, even partially.
+    pub const fn is_on(self) -> bool {
+        self.0 > 0
+    }
+    /// Returns `Level::OFF` if the output is on at this level, or `Level::ON` otherwise.
+    pub const fn toggled(self) -> Self {
+        if self.is_on() {
+            Level::OFF
+        } else {
+            Level::ON
+        }
+    }
+    /// Returns the level the output was at before it was toggled to this level, if `toggled` is `true`.
+    pub(crate) const fn untoggled(self, toggled: bool) -> Self {
+        if toggled {
+            self.toggled()
+        } else {
+            self
+        }
+    }
+}
+
+impl From<PinState> for Level {
+    fn from(state: PinState) -> Self {
+        match state {
+            PinState::High => Level::ON,
+            PinState::Low => Level::OFF,
+        }
+    }
+}
+
+impl From<Level> for PinState {
+    fn from(level: Level) -> Self {
+        PinState::from(level.is_on())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use embassy_futures::block_on;
+    use embassy_futures::select::{select, Either};
+    use embassy_time::Timer;
     use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction};
+    use embedded_hal_mock::eh1::MockError;
 
     #[test]
     fn test_blinker_finite_schedule() {
@@ -141,7 +994,7 @@ mod tests {
         });
 
         // スケジュールが空になっているはず
-        assert!(blinker.schedule.is_empty());
+        assert!(blinker.is_idle());
         drop(blinker);
         pin.done();
     }
@@ -166,7 +1019,1114 @@ mod tests {
             blinker.step().await.expect("infallible");
         });
         // スケジュールはまだ残っているはず
-        assert!(!blinker.schedule.is_empty());
+        assert!(!blinker.is_idle());
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_finite_on_off_schedule() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        let _ = blinker.push_schedule(Schedule::FiniteOnOff(
+            2,
+            Duration::from_millis(10),
+            Duration::from_millis(30),
+        ));
+
+        block_on(async {
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+        });
+
+        assert!(blinker.is_idle());
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_infinite_on_off_schedule() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        let _ = blinker.push_schedule(Schedule::InfiniteOnOff(
+            Duration::from_millis(10),
+            Duration::from_millis(50),
+        ));
+
+        block_on(async {
+            // 点灯時間と消灯時間がそれぞれ守られているはず
+            let start = embassy_time::Instant::now();
+            blinker.step().await.expect("infallible");
+            assert!(start.elapsed() >= Duration::from_millis(10));
+            let start = embassy_time::Instant::now();
+            blinker.step().await.expect("infallible");
+            assert!(start.elapsed() >= Duration::from_millis(50));
+            blinker.step().await.expect("infallible");
+        });
+        assert!(!blinker.is_idle());
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_finite_pattern_schedule() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        // 2回点滅してから休む、を2周
+        const PATTERN: Pattern = &[
+            Segment::high(Duration::from_millis(10)),
+            Segment::low(Duration::from_millis(10)),
+            Segment::high(Duration::from_millis(10)),
+            Segment::low(Duration::from_millis(40)),
+        ];
+        let _ = blinker.push_schedule(Schedule::FinitePattern(1, PATTERN));
+
+        block_on(async {
+            for _ in 0..8 {
+                blinker.step().await.expect("infallible");
+            }
+        });
+
+        assert!(blinker.is_idle());
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_infinite_pattern_schedule() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        const PATTERN: Pattern = &[
+            Segment::high(Duration::from_millis(10)),
+            Segment::high(Duration::from_millis(10)),
+            Segment::low(Duration::from_millis(10)),
+        ];
+        let _ = blinker.push_schedule(Schedule::InfinitePattern(PATTERN));
+
+        block_on(async {
+            for _ in 0..4 {
+                blinker.step().await.expect("infallible");
+            }
+        });
+
+        // 一周して2番目のセグメントから続くはず
+        let next: std::vec::Vec<_> = blinker
+            .timeline()
+            .clone()
+            .take(3)
+            .map(|edge| edge.level)
+            .collect();
+        assert_eq!(next, [Level::ON, Level::OFF, Level::ON]);
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_blinks_schedule() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        // 2回点滅して消灯で終わるはず
+        let _ = blinker.push_schedule(Schedule::Blinks(
+            2,
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+        ));
+
+        block_on(async {
+            for _ in 0..3 {
+                blinker.step().await.expect("infallible");
+                assert!(!blinker.is_idle());
+            }
+            blinker.step().await.expect("infallible");
+        });
+
+        assert!(blinker.is_idle());
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_zero_blinks_schedule() {
+        let mut pin = PinMock::new(&[]);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        let _ = blinker.push_schedule(Schedule::Blinks(
+            0,
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+        ));
+
+        block_on(async {
+            blinker.step().await.expect("infallible");
+        });
+
+        assert!(blinker.is_idle());
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_step_cancelled() {
+        let expectations = [Transaction::toggle()];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        let _ = blinker.push_schedule(Schedule::Finite(0, Duration::from_millis(100)));
+
+        block_on(async {
+            let start = Instant::now();
+            // ステップの途中でキャンセル
+            let cancelled = select(blinker.step(), Timer::after(Duration::from_millis(30))).await;
+            assert!(matches!(cancelled, Either::Second(())));
+            assert!(!blinker.is_idle());
+            // 再度トグルせず、残り時間だけ待つはず
+            blinker.step().await.expect("infallible");
+            assert!(start.elapsed() >= Duration::from_millis(100));
+        });
+
+        assert!(blinker.is_idle());
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_query_state() {
+        let expectations = [Transaction::set(State::High)];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        assert!(blinker.is_idle());
+        assert_eq!(blinker.level(), None);
+        let on_off =
+            Schedule::FiniteOnOff(2, Duration::from_millis(100), Duration::from_millis(50));
+        let _ = blinker.push_schedule(on_off.clone());
+        assert_eq!(blinker.depth(), 1);
+        assert_eq!(blinker.active_schedule(), Some(&on_off));
+        assert_eq!(blinker.remaining_count(), Some(3));
+        assert_eq!(blinker.time_until_next_transition(), None);
+
+        block_on(async {
+            // ステップの途中でキャンセル
+            let _ = select(blinker.step(), Timer::after(Duration::from_millis(30))).await;
+        });
+
+        assert_eq!(blinker.level(), Some(Level::ON));
+        // 実行中のステップも残り回数に含まれるはず
+        assert_eq!(blinker.remaining_count(), Some(3));
+        let remaining = blinker
+            .time_until_next_transition()
+            .expect("step in flight");
+        assert!(remaining <= Duration::from_millis(70));
+        assert!(blinker.next_deadline().is_some());
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_pop_and_remove() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+            // 次のステップがレベルを決めるので、元のレベル(Low)には戻さないはず
+            Transaction::set(State::High),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 3>::new(&mut pin);
+        let on_off = Schedule::InfiniteOnOff(Duration::from_millis(10), Duration::from_millis(10));
+
+        block_on(async {
+            let base = blinker.push_schedule(on_off.clone()).unwrap();
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+            let overlay = blinker.push_schedule(on_off.clone()).unwrap();
+            let top = blinker.push_schedule(on_off.clone()).unwrap();
+            assert_eq!(blinker.peek(), Some((top, &on_off)));
+            blinker.step().await.expect("infallible");
+
+            assert_eq!(blinker.remove(overlay), Some(on_off.clone()));
+            assert_eq!(blinker.remove(overlay), None);
+            assert_eq!(blinker.pop_schedule(), Some(on_off.clone()));
+            assert_eq!(blinker.peek().map(|(id, _)| id), Some(base));
+            blinker.step().await.expect("infallible");
+            assert_eq!(blinker.level(), Some(Level::ON));
+
+            let replaced = blinker.replace_top(on_off.clone()).unwrap();
+            assert_ne!(replaced, base);
+            assert_eq!(blinker.remove(base), None);
+        });
+
+        assert_eq!(blinker.depth(), 1);
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_step_cancelled_by_push() {
+        let expectations = [
+            Transaction::toggle(),
+            Transaction::get_state(State::Low),
+            // 中断されたトグルの前のレベル(High)のままなので、戻さずにトグルし直すはず
+            Transaction::set(State::High),
+            Transaction::toggle(),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(100)));
+
+        block_on(async {
+            let cancelled = select(blinker.step(), Timer::after(Duration::from_millis(10))).await;
+            assert!(matches!(cancelled, Either::Second(())));
+            // 新しいスケジュールはすぐに始まるはず
+            let _ = blinker.push_schedule(Schedule::FiniteOnOff(
+                0,
+                Duration::from_millis(10),
+                Duration::from_millis(10),
+            ));
+            blinker.step().await.expect("infallible");
+            assert_eq!(blinker.depth(), 1);
+            // 中断されたステップは最初からやり直すはず
+            let start = Instant::now();
+            blinker.step().await.expect("infallible");
+            assert!(start.elapsed() >= Duration::from_millis(100));
+        });
+
+        // 割り込まれる前(High)から1回だけトグルしたはず
+        assert_eq!(blinker.level(), Some(Level::OFF));
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_toggle_cancelled_by_push() {
+        let expectations = [
+            Transaction::set(State::Low),
+            Transaction::toggle(),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::toggle(),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        blinker.reset().expect("infallible");
+        let _ = blinker.push_schedule(Schedule::Finite(0, Duration::from_millis(100)));
+
+        block_on(async {
+            let cancelled = select(blinker.step(), Timer::after(Duration::from_millis(10))).await;
+            assert!(matches!(cancelled, Either::Second(())));
+            let _ = blinker.push_schedule(Schedule::Blinks(
+                1,
+                Duration::from_millis(10),
+                Duration::from_millis(10),
+            ));
+            blinker.run_until_idle().await.expect("infallible");
+        });
+
+        // トグルは合わせて1回だけなので、点灯で終わるはず
+        assert_eq!(blinker.level(), Some(Level::ON));
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_restore_level() {
+        let expectations = [
+            Transaction::toggle(),
+            // 割り込まれたときのピンの状態を読むはず
+            Transaction::get_state(State::High),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            // 元の状態に戻すはず
+            Transaction::set(State::High),
+            Transaction::toggle(),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(10)));
+
+        block_on(async {
+            blinker.step().await.expect("infallible");
+            let _ = blinker.push_schedule(Schedule::Blinks(
+                1,
+                Duration::from_millis(10),
+                Duration::from_millis(10),
+            ));
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+            assert_eq!(blinker.depth(), 1);
+            blinker.step().await.expect("infallible");
+        });
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_restore_known_level() {
+        let expectations = [
+            Transaction::set(State::Low),
+            Transaction::toggle(),
+            Transaction::toggle(),
+            Transaction::set(State::High),
+            Transaction::toggle(),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 3>::new(&mut pin);
+
+        // リセット後はピンの状態がわかっているので、読む必要はないはず
+        blinker.reset().expect("infallible");
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(10)));
+
+        block_on(async {
+            blinker.step().await.expect("infallible");
+            let _ = blinker.push_schedule(Schedule::Finite(0, Duration::from_millis(10)));
+            blinker.step().await.expect("infallible");
+            assert_eq!(blinker.depth(), 1);
+            blinker.step().await.expect("infallible");
+        });
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_absolute_timing_catch_up() {
+        let expectations = [
+            Transaction::toggle(),
+            Transaction::toggle(),
+            Transaction::toggle(),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let config = Config {
+            timing: Timing::Absolute(Lag::CatchUp),
+            ..Default::default()
+        };
+        let mut blinker = Blinker::<_, 2>::with_config(&mut pin, config);
+
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(20)));
+
+        block_on(async {
+            // 待たずにステップを始めて、期限を調べる
+            let _ = select(blinker.step(), core::future::ready(())).await;
+            let first = blinker.next_deadline().unwrap();
+            blinker.step().await.expect("infallible");
+            // 呼び出しの遅れをシミュレート
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            // 遅れた分はすぐに追いつき、位相はずれないはず
+            let _ = select(blinker.step(), core::future::ready(())).await;
+            assert_eq!(
+                blinker.next_deadline(),
+                Some(first + Duration::from_millis(20))
+            );
+            let start = Instant::now();
+            blinker.step().await.expect("infallible");
+            assert!(start.elapsed() < Duration::from_millis(20));
+            let _ = select(blinker.step(), core::future::ready(())).await;
+            assert_eq!(
+                blinker.next_deadline(),
+                Some(first + Duration::from_millis(40))
+            );
+        });
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_absolute_timing_skip() {
+        let expectations = [
+            Transaction::toggle(),
+            Transaction::toggle(),
+            Transaction::toggle(),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let config = Config {
+            timing: Timing::Absolute(Lag::Skip),
+            ..Default::default()
+        };
+        let mut blinker = Blinker::<_, 2>::with_config(&mut pin, config);
+
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(20)));
+
+        block_on(async {
+            // 待たずにステップを始めて、期限を調べる
+            let _ = select(blinker.step(), core::future::ready(())).await;
+            let first = blinker.next_deadline().unwrap();
+            blinker.step().await.expect("infallible");
+            // 遅れがなければ前回の期限から続くはず
+            let _ = select(blinker.step(), core::future::ready(())).await;
+            assert_eq!(
+                blinker.next_deadline(),
+                Some(first + Duration::from_millis(20))
+            );
+            blinker.step().await.expect("infallible");
+            // 遅れた分は飛ばして、今から1ステップ分待つはず
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            let start = Instant::now();
+            let _ = select(blinker.step(), core::future::ready(())).await;
+            assert!(blinker.next_deadline().unwrap() >= start + Duration::from_millis(20));
+        });
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_idle_wait() {
+        let expectations = [Transaction::toggle()];
+        let mut pin = PinMock::new(&expectations);
+        let config = Config {
+            idle: Idle::Wait,
+            ..Default::default()
+        };
+        let mut blinker = Blinker::<_, 2>::with_config(&mut pin, config);
+
+        block_on(async {
+            // スケジュールがない間は待ち続けるはず
+            let idle = select(blinker.step(), Timer::after(Duration::from_millis(20))).await;
+            assert!(matches!(idle, Either::Second(())));
+            let _ = blinker.push_schedule(Schedule::Finite(0, Duration::from_millis(10)));
+            blinker.step().await.expect("infallible");
+            let idle = select(blinker.step(), Timer::after(Duration::from_millis(20))).await;
+            assert!(matches!(idle, Either::Second(())));
+        });
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_run_until_idle() {
+        let expectations = [Transaction::toggle(), Transaction::toggle()];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        let _ = blinker.push_schedule(Schedule::Finite(1, Duration::from_millis(10)));
+        block_on(blinker.run_until_idle()).expect("infallible");
+
+        assert!(blinker.is_idle());
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_run_parks_when_idle() {
+        let expectations = [Transaction::toggle()];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        let _ = blinker.push_schedule(Schedule::Finite(0, Duration::from_millis(10)));
+        // スケジュールが無くなっても空回りせず待つはず
+        let result = block_on(select(
+            blinker.run(),
+            Timer::after(Duration::from_millis(50)),
+        ));
+        assert!(matches!(result, Either::Second(())));
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_run_propagates_error() {
+        let expectations =
+            [Transaction::toggle().with_error(MockError::Io(std::io::ErrorKind::NotConnected))];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(10)));
+        assert!(matches!(
+            block_on(blinker.run()),
+            Err(BlinkerError::Pin(MockError::Io(_)))
+        ));
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_push_errors() {
+        let mut pin = PinMock::new(&[]);
+        let mut blinker = Blinker::<_, 1>::new(&mut pin);
+
+        let zero = Schedule::Infinite(Duration::from_ticks(0));
+        assert!(matches!(
+            blinker.push_schedule(zero),
+            Err(BlinkerError::InvalidSchedule(_))
+        ));
+        let empty = Schedule::InfinitePattern(&[]);
+        assert!(matches!(
+            blinker.replace_top(empty),
+            Err(BlinkerError::InvalidSchedule(_))
+        ));
+        assert!(blinker.is_idle());
+
+        let schedule = Schedule::Infinite(Duration::from_millis(10));
+        blinker.push_schedule(schedule.clone()).unwrap();
+        // スタックが満杯なら、スケジュールを返すはず
+        let overflow = blinker.push_schedule(schedule.clone()).unwrap_err();
+        assert!(matches!(overflow, BlinkerError::StackFull(_)));
+        assert_eq!(overflow.into_schedule(), Some(schedule));
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_schedule_size() {
+        // パターンを埋め込まないので、スケジュールは小さいままのはず
+        assert!(core::mem::size_of::<Schedule>() <= 32);
+        assert!(core::mem::size_of::<BlinkerError<MockError>>() <= 64);
+    }
+
+    #[test]
+    fn test_blinker_overflow_policies() {
+        let ms = Duration::from_millis;
+        for (overflow, kept) in [
+            (Overflow::EvictOldest, [ms(20), ms(30)]),
+            (Overflow::ReplaceTop, [ms(10), ms(30)]),
+        ] {
+            let mut pin = PinMock::new(&[]);
+            let config = Config {
+                overflow,
+                ..Default::default()
+            };
+            let mut blinker = Blinker::<_, 2>::with_config(&mut pin, config);
+            for dur in [ms(10), ms(20), ms(30)] {
+                blinker.push_schedule(Schedule::Infinite(dur)).unwrap();
+            }
+
+            assert_eq!(blinker.depth(), 2);
+            assert_eq!(blinker.pop_schedule(), Some(Schedule::Infinite(kept[1])));
+            assert_eq!(blinker.pop_schedule(), Some(Schedule::Infinite(kept[0])));
+            drop(blinker);
+            pin.done();
+        }
+    }
+
+    #[test]
+    fn test_blinker_queue_order() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            // キューが空になったら、背景のスケジュールが無駄なエッジなしに再開するはず
+            Transaction::set(State::Low),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let config = Config {
+            order: Order::Queue,
+            ..Default::default()
+        };
+        let mut blinker = Blinker::<_, 3>::with_config(&mut pin, config);
+        let ms = Duration::from_millis;
+
+        block_on(async {
+            blinker
+                .push_schedule(Schedule::InfiniteOnOff(ms(10), ms(10)))
+                .unwrap();
+            blinker.step().await.expect("infallible");
+            let first = blinker
+                .push_schedule(Schedule::Blinks(1, ms(10), ms(10)))
+                .unwrap();
+            let second = blinker
+                .push_schedule(Schedule::Blinks(1, ms(20), ms(20)))
+                .unwrap();
+            assert_eq!(blinker.peek().map(|(id, _)| id), Some(first));
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+            assert_eq!(blinker.peek().map(|(id, _)| id), Some(second));
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+        });
+
+        assert_eq!(blinker.depth(), 1);
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_conditions() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+            // 優先度の高い条件が解除されたら、元のスケジュールが再開するはず
+            Transaction::set(State::High),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+        let ms = Duration::from_millis;
+
+        block_on(async {
+            blinker
+                .assert_condition("charging", 1, Schedule::InfiniteOnOff(ms(10), ms(10)))
+                .unwrap();
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+            blinker
+                .assert_condition("fault", 2, Schedule::InfiniteOnOff(ms(10), ms(10)))
+                .unwrap();
+            // 優先度の低い条件は表示中の条件を邪魔しないはず
+            let _ = blinker.deassert_condition("pairing");
+            blinker.step().await.expect("infallible");
+            assert!(blinker.is_asserted("fault"));
+            blinker.deassert_condition("fault").unwrap();
+            blinker.step().await.expect("infallible");
+        });
+
+        assert!(!blinker.is_asserted("fault"));
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_preview() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::toggle(),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2, _>::new_polled(&mut pin);
+        let ms = Duration::from_millis;
+        let start = Instant::from_millis(1000);
+
+        blinker
+            .push_schedule(Schedule::Blinks(1, ms(10), ms(20)))
+            .unwrap();
+        assert_eq!(
+            blinker.poll(start).expect("infallible"),
+            Some(start + ms(10))
+        );
+        // 実行中のステップは含まれず、その後のステップから始まるはず
+        let edges: std::vec::Vec<_> = blinker.preview().collect();
+        assert_eq!(
+            edges,
+            [Edge {
+                level: Level::OFF,
+                duration: ms(20)
+            }]
+        );
+        assert_eq!(blinker.preview().total_duration(), Some(ms(20)));
+        assert_eq!(blinker.timeline().total_duration(), Some(ms(30)));
+        assert_eq!(
+            blinker.poll(start + ms(10)).expect("infallible"),
+            Some(start + ms(30))
+        );
+        assert_eq!(blinker.poll(start + ms(30)).expect("infallible"), None);
+
+        // 実行中のトグルを二重に数えず、残りの一回だけになるはず
+        blinker.push_schedule(Schedule::Finite(1, ms(10))).unwrap();
+        assert_eq!(
+            blinker.poll(start + ms(30)).expect("infallible"),
+            Some(start + ms(40))
+        );
+        let edges: std::vec::Vec<_> = blinker.preview().collect();
+        assert_eq!(
+            edges,
+            [Edge {
+                level: Level::OFF,
+                duration: ms(10)
+            }]
+        );
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_reassert_condition() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            // スケジュールが変わったら、最初から表示し直すはず
+            Transaction::set(State::High),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2, _>::new_polled(&mut pin);
+        let ms = Duration::from_millis;
+        let start = Instant::from_millis(1000);
+        let charging = Schedule::InfiniteOnOff(ms(10), ms(10));
+
+        let id = blinker
+            .assert_condition("charging", 1, charging.clone())
+            .unwrap();
+        assert_eq!(
+            blinker.poll(start).expect("infallible"),
+            Some(start + ms(10))
+        );
+        // 同じスケジュールで表示中に再度アサートしても、ステップを始め直さないはず
+        assert_eq!(
+            blinker.assert_condition("charging", 1, charging).unwrap(),
+            id
+        );
+        assert_eq!(
+            blinker.poll(start + ms(5)).expect("infallible"),
+            Some(start + ms(10))
+        );
+        assert_eq!(
+            blinker.poll(start + ms(10)).expect("infallible"),
+            Some(start + ms(20))
+        );
+        let replaced = blinker
+            .assert_condition("charging", 1, Schedule::InfiniteOnOff(ms(30), ms(30)))
+            .unwrap();
+        assert_ne!(replaced, id);
+        assert_eq!(
+            blinker.poll(start + ms(15)).expect("infallible"),
+            Some(start + ms(45))
+        );
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_push_for() {
+        let mut pin = PinMock::new(&[]);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+
+        blinker
+            .push_for(Schedule::Infinite(Duration::from_millis(10)), Duration::MAX)
+            .unwrap();
+        // 期限が表せないほど長ければ、期限なしで追加されるはず
+        assert!(!blinker.timeline().clone().expire(Instant::MAX));
+        blinker
+            .push_for(
+                Schedule::Infinite(Duration::from_millis(10)),
+                Duration::from_secs(1),
+            )
+            .unwrap();
+        assert!(blinker.timeline().clone().expire(Instant::MAX));
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_push_until() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            // 期限が来たらパターンの途中でも取り除かれ、元のスケジュールが再開するはず
+            Transaction::set(State::High),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2, _>::new_polled(&mut pin);
+        let ms = Duration::from_millis;
+        let start = Instant::from_millis(1000);
+
+        blinker
+            .push_schedule(Schedule::InfiniteOnOff(ms(100), ms(100)))
+            .unwrap();
+        assert_eq!(
+            blinker.poll(start).expect("infallible"),
+            Some(start + ms(100))
+        );
+        blinker
+            .push_until(Schedule::InfiniteOnOff(ms(30), ms(30)), start + ms(50))
+            .unwrap();
+        assert_eq!(
+            blinker.poll(start).expect("infallible"),
+            Some(start + ms(30))
+        );
+        // 期限でステップが切り詰められるはず
+        let deadline = blinker.poll(start + ms(30)).expect("infallible");
+        assert_eq!(deadline, Some(start + ms(50)));
+        let deadline = blinker.poll(start + ms(50)).expect("infallible");
+        assert_eq!(deadline, Some(start + ms(150)));
+        assert_eq!(blinker.depth(), 1);
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_step_outcome() {
+        let expectations = [
+            Transaction::get_state(State::Low),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::new(&mut pin);
+        let ms = Duration::from_millis;
+
+        block_on(async {
+            assert_eq!(blinker.step().await.expect("infallible"), StepOutcome::Idle);
+            let empty = blinker
+                .push_schedule(Schedule::Blinks(0, ms(10), ms(10)))
+                .unwrap();
+            let blink = blinker
+                .push_schedule(Schedule::Blinks(1, ms(10), ms(10)))
+                .unwrap();
+            assert_eq!(
+                blinker.step().await.expect("infallible"),
+                StepOutcome::Stepped
+            );
+            assert_eq!(
+                blinker.step().await.expect("infallible"),
+                StepOutcome::ScheduleCompleted(blink)
+            );
+            assert!(!blinker.contains(blink));
+            // 何もしないスケジュールも完了が報告されるはず
+            assert!(blinker.contains(empty));
+            assert_eq!(
+                blinker.step().await.expect("infallible"),
+                StepOutcome::ScheduleCompleted(empty)
+            );
+        });
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_run_ignores_error() {
+        let expectations = [
+            Transaction::toggle().with_error(MockError::Io(std::io::ErrorKind::NotConnected)),
+            Transaction::toggle(),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let config = Config {
+            errors: ErrorPolicy::Ignore,
+            ..Default::default()
+        };
+        let mut blinker = Blinker::<_, 2>::with_config(&mut pin, config);
+
+        let _ = blinker.push_schedule(Schedule::Finite(1, Duration::from_millis(10)));
+        // 失敗したステップも数えられるはず
+        block_on(blinker.run_until_idle()).expect("errors are ignored");
+
+        assert!(blinker.is_idle());
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_active_low() {
+        let expectations = [
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+            Transaction::set(State::High),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let config = Config {
+            polarity: Polarity::ActiveLow,
+            ..Default::default()
+        };
+        let mut blinker = Blinker::<_, 2>::with_config(&mut pin, config);
+
+        // アクティブローなので、点灯はLow、消灯はHighになるはず
+        let _ = blinker.push_schedule(Schedule::Blinks(
+            1,
+            Duration::from_millis(10),
+            Duration::from_millis(10),
+        ));
+        block_on(blinker.run_until_idle()).expect("infallible");
+        blinker.reset().expect("infallible");
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_active_high() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::Low),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let config = Config {
+            polarity: Polarity::ActiveHigh,
+            ..Default::default()
+        };
+        let mut blinker = Blinker::<_, 2>::with_config(&mut pin, config);
+
+        let _ = blinker.push_schedule(Schedule::Blinks(
+            1,
+            Duration::from_millis(10),
+            Duration::from_millis(10),
+        ));
+        block_on(blinker.run_until_idle()).expect("infallible");
+        blinker.reset().expect("infallible");
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_active_low_restore_level() {
+        let expectations = [
+            Transaction::toggle(),
+            // 消灯(High)の状態を読み、点灯(Low)して消灯(High)したので、戻す必要はないはず
+            Transaction::get_state(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+            Transaction::toggle(),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let config = Config {
+            polarity: Polarity::ActiveLow,
+            ..Default::default()
+        };
+        let mut blinker = Blinker::<_, 2>::with_config(&mut pin, config);
+
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(10)));
+
+        block_on(async {
+            blinker.step().await.expect("infallible");
+            let _ = blinker.push_schedule(Schedule::Blinks(
+                1,
+                Duration::from_millis(10),
+                Duration::from_millis(10),
+            ));
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+        });
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_poll() {
+        let expectations = [Transaction::set(State::High), Transaction::set(State::Low)];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2, _>::new_polled(&mut pin);
+
+        let _ = blinker.push_schedule(Schedule::Blinks(
+            1,
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+        ));
+
+        let start = Instant::from_millis(1000);
+        let ms = Duration::from_millis;
+        assert_eq!(
+            blinker.poll(start).expect("infallible"),
+            Some(start + ms(10))
+        );
+        // 期限前は何もしないはず
+        assert_eq!(
+            blinker.poll(start + ms(5)).expect("infallible"),
+            Some(start + ms(10))
+        );
+        assert_eq!(
+            blinker.poll(start + ms(10)).expect("infallible"),
+            Some(start + ms(30))
+        );
+        assert_eq!(blinker.poll(start + ms(31)).expect("infallible"), None);
+        assert!(blinker.is_idle());
+        assert_eq!(blinker.poll(start + ms(40)).expect("infallible"), None);
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_poll_skips_empty_schedule() {
+        let expectations = [Transaction::set(State::Low), Transaction::set(State::High)];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2, _>::new_polled(&mut pin);
+        let ms = Duration::from_millis;
+
+        blinker.reset().expect("infallible");
+        let _ = blinker.push_schedule(Schedule::InfiniteOnOff(ms(10), ms(10)));
+        let _ = blinker.push_schedule(Schedule::Blinks(0, ms(10), ms(10)));
+
+        // 何もしないスケジュールを飛ばして、下のスケジュールの期限を返すはず
+        let start = Instant::from_millis(1000);
+        assert_eq!(
+            blinker.poll(start).expect("infallible"),
+            Some(start + ms(10))
+        );
+        assert_eq!(blinker.depth(), 1);
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_blinker_poll_absolute_timing() {
+        let expectations = [
+            Transaction::toggle(),
+            Transaction::toggle(),
+            Transaction::toggle(),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let config = Config {
+            timing: Timing::Absolute(Lag::CatchUp),
+            ..Default::default()
+        };
+        let mut blinker = Blinker::<_, 2, _>::with_clock(&mut pin, config, ());
+
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(10)));
+
+        let start = Instant::from_millis(1000);
+        let ms = Duration::from_millis;
+        assert_eq!(
+            blinker.poll(start).expect("infallible"),
+            Some(start + ms(10))
+        );
+        // 遅れて呼ばれても位相はずれないはず
+        assert_eq!(
+            blinker.poll(start + ms(13)).expect("infallible"),
+            Some(start + ms(20))
+        );
+        assert_eq!(
+            blinker.poll(start + ms(20)).expect("infallible"),
+            Some(start + ms(30))
+        );
+
         drop(blinker);
         pin.done();
     }
@@ -180,7 +2140,7 @@ mod tests {
         let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(100)));
 
         blinker.reset().expect("infallible");
-        assert!(blinker.schedule.is_empty());
+        assert!(blinker.is_idle());
         drop(blinker);
         pin.done();
     }
@@ -0,0 +1,36 @@
+//! Outputs a [`Blinker`](crate::Blinker) can drive.
+use core::fmt::Debug;
+
+use embedded_hal::digital::{PinState, StatefulOutputPin};
+
+use crate::{Level, Polarity};
+
+/// An output driven by a [`Blinker`](crate::Blinker): any [`StatefulOutputPin`],
+/// or a PWM channel through [`PwmOutput`](crate::PwmOutput).
+///
+/// Levels are logical, and `polarity` tells how the output is wired(see [`Polarity`]).
+pub trait Output {
+    /// Error type of the output.
+    type Error: Debug;
+    /// Drives the output to `level`.
+    fn set_level(&mut self, level: Level, polarity: Polarity) -> Result<(), Self::Error>;
+    /// Turns the output off if it is on, or fully on if it is off.
+    fn toggle(&mut self, polarity: Polarity) -> Result<(), Self::Error>;
+    /// Returns the level the output is driven at.
+    fn level(&mut self, polarity: Polarity) -> Result<Level, Self::Error>;
+}
+
+/// Digital pins are on(see [`Polarity`]) at any level above `Level::OFF`.
+impl<P: StatefulOutputPin> Output for P {
+    type Error = P::Error;
+    fn set_level(&mut self, level: Level, polarity: Polarity) -> Result<(), Self::Error> {
+        self.set_state(polarity.apply(PinState::from(level.is_on())))
+    }
+    fn toggle(&mut self, _polarity: Polarity) -> Result<(), Self::Error> {
+        StatefulOutputPin::toggle(self)
+    }
+    fn level(&mut self, polarity: Polarity) -> Result<Level, Self::Error> {
+        let state = PinState::from(self.is_set_high()?);
+        Ok(Level::from(polarity.apply(state)))
+    }
+}
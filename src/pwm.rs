@@ -0,0 +1,164 @@
+//! Driving a PWM channel with a [`Blinker`](crate::Blinker).
+use embedded_hal::pwm::SetDutyCycle;
+
+use crate::{Level, Output, Polarity};
+
+/// A PWM channel driven as an [`Output`], so that schedules can set its brightness(see [`Level`]).
+///
+/// The level is tracked in software, and is assumed to be `Level::OFF` until the output is driven.
+/// The whole output can be dimmed with `PwmOutput::set_scale`, e.g. at night.
+pub struct PwmOutput<D: SetDutyCycle> {
+    pwm: D,
+    level: Level,
+    scale: u8,
+}
+
+impl<D: SetDutyCycle> PwmOutput<D> {
+    /// Create a new `PwmOutput` struct, at full scale.
+    pub fn new(pwm: D) -> Self {
+        Self {
+            pwm,
+            level: Level::OFF,
+            scale: u8::MAX,
+        }
+    }
+    /// Sets the brightness the output has at `Level::ON`, from 0 to 255(the default, full brightness).
+    /// Every level is scaled down accordingly.
+    /// The new scale takes effect the next time the output is driven.
+    pub fn set_scale(&mut self, scale: u8) {
+        self.scale = scale;
+    }
+    /// Returns the brightness the output has at `Level::ON`(see `PwmOutput::set_scale`).
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+    /// Returns the wrapped PWM channel.
+    pub fn into_inner(self) -> D {
+        self.pwm
+    }
+}
+
+impl<D: SetDutyCycle> Output for PwmOutput<D> {
+    type Error = D::Error;
+    fn set_level(&mut self, level: Level, polarity: Polarity) -> Result<(), Self::Error> {
+        let max = self.pwm.max_duty_cycle();
+        // computed at the resolution of the channel, so that dim levels are not rounded down to zero
+        let on = u64::from(level.0) * u64::from(self.scale) * u64::from(max)
+            / (u64::from(u16::MAX) * u64::from(u8::MAX));
+        // at most `max`, since the level and the scale are at most their maximums
+        let on = on as u16;
+        let duty = match polarity {
+            Polarity::ActiveHigh => on,
+            Polarity::ActiveLow => max - on,
+        };
+        self.pwm.set_duty_cycle(duty)?;
+        self.level = level;
+        Ok(())
+    }
+    fn toggle(&mut self, polarity: Polarity) -> Result<(), Self::Error> {
+        self.set_level(self.level.toggled(), polarity)
+    }
+    fn level(&mut self, _polarity: Polarity) -> Result<Level, Self::Error> {
+        Ok(self.level)
+    }
+}
+
+#[cfg(feature = "embassy")]
+impl<D: SetDutyCycle, const N: usize> crate::Blinker<PwmOutput<D>, N> {
+    /// Create a new `Blinker` struct over a PWM channel(see [`PwmOutput`]).
+    pub fn with_pwm(pwm: D) -> Self {
+        Self::new(PwmOutput::new(pwm))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Blinker, Config, Pattern, Schedule, Segment};
+    use core::convert::Infallible;
+    use embassy_futures::block_on;
+    use embassy_time::Duration;
+    use embedded_hal::pwm::ErrorType;
+
+    /// Records duty cycles without driving anything, with the given maximum duty cycle.
+    struct RecordingPwm<'a>(&'a mut std::vec::Vec<u16>, u16);
+
+    impl ErrorType for RecordingPwm<'_> {
+        type Error = Infallible;
+    }
+
+    impl SetDutyCycle for RecordingPwm<'_> {
+        fn max_duty_cycle(&self) -> u16 {
+            self.1
+        }
+        fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+            self.0.push(duty);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_pwm_brightness_pattern() {
+        let mut duties = std::vec::Vec::new();
+        let mut blinker = Blinker::<_, 1>::with_pwm(RecordingPwm(&mut duties, 1000));
+        const PATTERN: Pattern = &[
+            Segment::dimmed(51, Duration::from_millis(10)),
+            Segment::high(Duration::from_millis(10)),
+            Segment::low(Duration::from_millis(10)),
+        ];
+
+        blinker
+            .push_schedule(Schedule::FinitePattern(0, PATTERN))
+            .unwrap();
+        block_on(blinker.run_until_idle()).expect("infallible");
+        // 夜間は全体を暗くするはず
+        blinker.output_mut().set_scale(127);
+        blinker
+            .push_schedule(Schedule::FinitePattern(0, PATTERN))
+            .unwrap();
+        block_on(blinker.run_until_idle()).expect("infallible");
+
+        drop(blinker);
+        assert_eq!(duties, [200, 1000, 0, 99, 498, 0]);
+    }
+
+    #[test]
+    fn test_pwm_fine_resolution() {
+        let mut duties = std::vec::Vec::new();
+        let mut output = PwmOutput::new(RecordingPwm(&mut duties, u16::MAX));
+        output.set_scale(127);
+
+        // 暗くしても、低いレベルが0に潰れないはず
+        for level in [
+            Level(1000),
+            Level::from_brightness(1),
+            Level::from_brightness(2),
+        ] {
+            output
+                .set_level(level, Polarity::ActiveHigh)
+                .expect("infallible");
+        }
+
+        assert_eq!(duties, [498, 127, 255]);
+    }
+
+    #[test]
+    fn test_pwm_active_low() {
+        let mut duties = std::vec::Vec::new();
+        let config = Config {
+            polarity: Polarity::ActiveLow,
+            ..Default::default()
+        };
+        let mut blinker =
+            Blinker::<_, 1>::with_config(PwmOutput::new(RecordingPwm(&mut duties, 1000)), config);
+
+        blinker
+            .push_schedule(Schedule::Finite(1, Duration::from_millis(10)))
+            .unwrap();
+        block_on(blinker.run_until_idle()).expect("infallible");
+
+        drop(blinker);
+        // 点灯時は0、消灯時は最大になるはず
+        assert_eq!(duties, [0, 1000]);
+    }
+}
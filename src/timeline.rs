@@ -0,0 +1,894 @@
+//! Hardware-free engine behind a [`Blinker`](crate::Blinker).
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+
+use crate::{Level, Overflow, Schedule, Segment};
+
+/// A change of the output produced by a [`Timeline`]:
+/// the level the output is driven to, and how long it stays there.
+/// The level is logical(see [`Polarity`](crate::Polarity)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    /// The level the output is driven to.
+    pub level: Level,
+    /// How long the output stays at `level`.
+    pub duration: Duration,
+}
+
+/// Identifies a schedule pushed to a [`Timeline`] or a [`Blinker`](crate::Blinker),
+/// so that exactly that schedule can be removed later(see `Timeline::remove`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScheduleId(u32);
+
+/// The stack of schedules of a [`Blinker`](crate::Blinker), without any pin or timer.
+///
+/// A timeline is an iterator of the [`Edge`]s its schedules produce, in the same way a blinker would drive its pin,
+/// so it can be used to test schedules, compute how long they last, render previews, or drive other kinds of outputs.
+/// Iterating consumes the schedules, so clone the timeline to look ahead without changing it.
+/// ```ignore
+/// let mut timeline = Timeline::<2>::new();
+/// let _ = timeline.push_schedule(Schedule::Blinks(2, Duration::from_millis(100), Duration::from_millis(300)));
+/// assert_eq!(timeline.total_duration(), Some(Duration::from_millis(800)));
+/// for edge in timeline.clone() {
+///     draw(edge.level, edge.duration);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Timeline<const N: usize> {
+    pub(crate) stack: Vec<Entry, N>,
+    /// Logical level of the output, if known.
+    pub(crate) level: Option<Level>,
+    /// Whether the level of the output has to be captured before the next step,
+    /// because a schedule was interrupted while the level was unknown.
+    pub(crate) capture_pending: bool,
+    /// Level the output has to be driven back to before the next step,
+    /// because the schedule on the top of the stack was removed.
+    pub(crate) restore: Option<Level>,
+    /// Whether the step in flight toggles the output(see `Blinker::step`).
+    /// If it is abandoned, the interrupted schedule resumes at the level from before the step,
+    /// so that the output is not toggled twice when the step is executed again.
+    pub(crate) toggling: bool,
+    /// Identifier given to the next schedule.
+    next_id: u32,
+}
+
+impl<const N: usize> Timeline<N> {
+    /// Create a new, empty `Timeline` struct.
+    /// The level of the output is unknown until `Timeline::set_level` is called or a level is driven.
+    pub const fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            level: None,
+            capture_pending: false,
+            restore: None,
+            toggling: false,
+            next_id: 0,
+        }
+    }
+    /// Push a new schedule to the stack with the lowest priority, and returns its identifier.
+    /// Returns an error if the stack is full
+    ///
+    /// The current level is recorded for the previous schedule, and restored when the new one is popped.
+    pub fn push_schedule(&mut self, schedule: Schedule) -> Result<ScheduleId, Schedule> {
+        self.push_with_priority(schedule, 0, Overflow::Reject)
+    }
+    /// Push a new schedule to the stack with the given priority, and returns its identifier.
+    /// If the stack is full, makes room according to `overflow`, or returns an error if it cannot.
+    pub fn push_with_priority(
+        &mut self,
+        schedule: Schedule,
+        priority: u8,
+        overflow: Overflow,
+    ) -> Result<ScheduleId, Schedule> {
+        if overflow == Overflow::ReplaceTop && self.stack.is_full() {
+            return self.replace_top_with(schedule, priority);
+        }
+        if !self.make_room(priority, overflow) {
+            return Err(schedule);
+        }
+        self.insert(self.stack.len(), priority, schedule)
+    }
+    /// Queues a new schedule with the given priority, and returns its identifier.
+    /// If the stack is full, makes room according to `overflow`, or returns an error if it cannot.
+    /// `Overflow::EvictOldest` drops the finite schedule queued first(on the top) rather than the background,
+    /// or the oldest background if no finite schedule is queued.
+    ///
+    /// Finite schedules are executed in the order they are queued, before the infinite ones.
+    /// An infinite schedule becomes the background: it is executed once the queued finite schedules are over,
+    /// and the previous background resumes when it is popped.
+    pub fn enqueue(
+        &mut self,
+        schedule: Schedule,
+        priority: u8,
+        overflow: Overflow,
+    ) -> Result<ScheduleId, Schedule> {
+        if overflow == Overflow::ReplaceTop && self.stack.is_full() {
+            return self.replace_top_with(schedule, priority);
+        }
+        if overflow == Overflow::EvictOldest && self.stack.is_full() {
+            let index = match self.stack.last() {
+                Some(entry) if !entry.schedule.is_infinite() => self.stack.len() - 1,
+                _ => 0,
+            };
+            self.remove_at(index);
+        }
+        if !self.make_room(priority, overflow) {
+            return Err(schedule);
+        }
+        let index = self
+            .stack
+            .iter()
+            .rposition(|entry| entry.schedule.is_infinite())
+            .map_or(0, |index| index + 1);
+        self.insert(index, priority, schedule)
+    }
+    /// Asserts a named condition, displayed with the given schedule while it is the one with the highest priority,
+    /// and returns the identifier of the schedule.
+    /// If the stack is full, makes room according to `overflow`(`Overflow::ReplaceTop` does not make room),
+    /// or returns an error if it cannot.
+    ///
+    /// Conditions are kept on the stack in the order of their priorities(the newest on the top among equals):
+    /// a condition is put right below the first one with a higher priority, or on the top if there is none.
+    /// So the schedule of a condition is interrupted while one with a higher priority is asserted,
+    /// and resumes when that one is deasserted.
+    /// Schedules pushed with `Timeline::push_schedule` afterwards are put on the top as usual.
+    /// Asserting a condition again replaces its schedule and priority.
+    /// If neither of them changes, nothing happens and the identifier of the schedule stays valid,
+    /// so a condition can be asserted repeatedly(e.g. every time a sensor is read) without restarting its schedule.
+    ///
+    /// A condition is deasserted when its schedule is over, so conditions usually have infinite schedules.
+    pub fn assert_condition(
+        &mut self,
+        name: &'static str,
+        priority: u8,
+        schedule: Schedule,
+        overflow: Overflow,
+    ) -> Result<ScheduleId, Schedule> {
+        if let Some(entry) = self
+            .stack
+            .iter_mut()
+            .find(|entry| entry.condition == Some(name) && entry.priority == priority)
+        {
+            if entry.schedule == schedule {
+                return Ok(entry.id);
+            }
+            let id = ScheduleId(self.next_id);
+            self.next_id = self.next_id.wrapping_add(1);
+            entry.id = id;
+            entry.schedule = schedule;
+            entry.cursor = 0;
+            return Ok(id);
+        }
+        self.deassert_condition(name);
+        let overflow = match overflow {
+            Overflow::ReplaceTop => Overflow::Reject,
+            overflow => overflow,
+        };
+        if !self.make_room(priority, overflow) {
+            return Err(schedule);
+        }
+        let index = self
+            .stack
+            .iter()
+            .position(|entry| entry.condition.is_some() && entry.priority > priority)
+            .unwrap_or(self.stack.len());
+        let id = self.insert(index, priority, schedule)?;
+        self.stack[index].condition = Some(name);
+        Ok(id)
+    }
+    /// Deasserts a named condition, removing its schedule from the stack, and returns the schedule.
+    /// Returns `None` if the condition is not asserted.
+    pub fn deassert_condition(&mut self, name: &'static str) -> Option<Schedule> {
+        let index = self
+            .stack
+            .iter()
+            .position(|entry| entry.condition == Some(name))?;
+        Some(self.remove_at(index))
+    }
+    /// Returns `true` if the named condition is asserted.
+    pub fn is_asserted(&self, name: &'static str) -> bool {
+        self.stack.iter().any(|entry| entry.condition == Some(name))
+    }
+    /// Replaces the schedule on the top of the stack, or pushes it if the stack is empty.
+    /// Returns the identifier of the new schedule; the identifier of the replaced one is no longer valid.
+    /// If the replaced schedule displays a condition, the condition is deasserted.
+    /// The new schedule does not expire, even if the replaced one did(see `Timeline::set_expiry`).
+    pub fn replace_top(&mut self, schedule: Schedule) -> Result<ScheduleId, Schedule> {
+        self.replace_top_with(schedule, 0)
+    }
+    fn replace_top_with(
+        &mut self,
+        schedule: Schedule,
+        priority: u8,
+    ) -> Result<ScheduleId, Schedule> {
+        let Some(entry) = self.stack.last_mut() else {
+            return self.push_with_priority(schedule, priority, Overflow::Reject);
+        };
+        let id = ScheduleId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        entry.id = id;
+        entry.priority = priority;
+        entry.condition = None;
+        entry.expires_at = None;
+        entry.schedule = schedule;
+        entry.cursor = 0;
+        Ok(id)
+    }
+    /// Pops the schedule on the top of the stack, and returns it.
+    /// The level recorded for the schedule below is restored before its next step.
+    pub fn pop_schedule(&mut self) -> Option<Schedule> {
+        let top = self.stack.len().checked_sub(1)?;
+        Some(self.remove_at(top))
+    }
+    /// Returns `true` if the schedule with the given identifier is on the stack,
+    /// i.e. it is neither over nor removed.
+    pub fn contains(&self, id: ScheduleId) -> bool {
+        self.stack.iter().any(|entry| entry.id == id)
+    }
+    /// Returns the schedule on the top of the stack and its identifier, without removing it.
+    pub fn peek(&self) -> Option<(ScheduleId, &Schedule)> {
+        self.stack.last().map(|entry| (entry.id, &entry.schedule))
+    }
+    /// Removes the schedule with the given identifier from anywhere in the stack, and returns it.
+    /// Returns `None` if the schedule is not on the stack anymore(e.g. it is over, or it was replaced).
+    ///
+    /// Removing the schedule on the top is the same as `Timeline::pop_schedule`.
+    /// The schedules around one removed from the middle are not affected:
+    /// the one below still resumes at the level it was interrupted at.
+    pub fn remove(&mut self, id: ScheduleId) -> Option<Schedule> {
+        let index = self.stack.iter().position(|entry| entry.id == id)?;
+        Some(self.remove_at(index))
+    }
+    /// Makes room for a schedule with the given priority if the stack is full.
+    /// Returns `false` if there is no room.
+    fn make_room(&mut self, priority: u8, overflow: Overflow) -> bool {
+        if !self.stack.is_full() {
+            return true;
+        }
+        let index = match overflow {
+            Overflow::Reject | Overflow::ReplaceTop => None,
+            Overflow::EvictOldest => Some(0),
+            Overflow::DropLowerPriority => self
+                .stack
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| entry.priority < priority)
+                .min_by_key(|(_, entry)| entry.priority)
+                .map(|(index, _)| index),
+        };
+        match index {
+            Some(index) if index < self.stack.len() => {
+                self.remove_at(index);
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Inserts a new schedule at `index`.
+    /// If it is on the top, the current level is recorded for the schedule it interrupts
+    /// (or the level from before the toggling step it abandons).
+    /// Otherwise it has not been started, so it does not need to be resumed.
+    fn insert(
+        &mut self,
+        index: usize,
+        priority: u8,
+        schedule: Schedule,
+    ) -> Result<ScheduleId, Schedule> {
+        let id = ScheduleId(self.next_id);
+        self.stack
+            .insert(index, Entry::new(id, priority, schedule))
+            .map_err(|entry| entry.schedule)?;
+        self.next_id = self.next_id.wrapping_add(1);
+        if index + 1 == self.stack.len() {
+            let level = self.restore.or(self.level);
+            let toggling = self.toggling;
+            if let [.., interrupted, _] = self.stack.as_mut_slice() {
+                interrupted.resume_level = level.map(|level| level.untoggled(toggling));
+                interrupted.abandoned_toggle = toggling;
+                self.capture_pending |= level.is_none();
+            }
+        }
+        Ok(id)
+    }
+    /// Removes the schedule at `index`, restoring the level recorded for the one below if it was on the top.
+    fn remove_at(&mut self, index: usize) -> Schedule {
+        let schedule = self.stack.remove(index).schedule;
+        if index == self.stack.len() {
+            self.restore = self.resume_level();
+        }
+        schedule
+    }
+    /// Sets the instant the schedule with the given identifier expires at.
+    /// Returns `false` if the schedule is not on the stack anymore.
+    ///
+    /// Expired schedules are removed by `Timeline::expire`, wherever they are on the stack.
+    /// Iterating the timeline does not take expiry into account, since it has no notion of the current time.
+    pub fn set_expiry(&mut self, id: ScheduleId, expires_at: Instant) -> bool {
+        let Some(entry) = self.stack.iter_mut().find(|entry| entry.id == id) else {
+            return false;
+        };
+        entry.expires_at = Some(expires_at);
+        true
+    }
+    /// Removes the schedules that have expired by `now`(see `Timeline::set_expiry`).
+    /// Returns `true` if any schedule was removed.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let mut expired = false;
+        while let Some(index) = self
+            .stack
+            .iter()
+            .position(|entry| entry.expires_at.is_some_and(|at| at <= now))
+        {
+            self.remove_at(index);
+            expired = true;
+        }
+        expired
+    }
+    /// Clears schedules.
+    pub fn clear(&mut self) {
+        self.stack.clear();
+        self.capture_pending = false;
+        self.restore = None;
+        self.toggling = false;
+    }
+    /// Returns the number of schedules on the stack.
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+    /// Returns `true` if there is no schedule on the stack.
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+    /// Returns the schedule on the top of the stack, which is the one being executed.
+    pub fn active_schedule(&self) -> Option<&Schedule> {
+        self.stack.last().map(|entry| &entry.schedule)
+    }
+    /// Returns how many more times the active schedule repeats before it is popped, including the one in progress,
+    /// or `None` if it is infinite(or if there is no schedule).
+    ///
+    /// The unit depends on the schedule: toggles for `Schedule::Finite`, steps for `Schedule::FiniteOnOff`,
+    /// passes through the pattern for `Schedule::FinitePattern`, and blinks for `Schedule::Blinks`.
+    /// Fades(`Schedule::FadeIn`, `Schedule::FadeOut`) are played once.
+    pub fn remaining_count(&self) -> Option<u32> {
+        match self.active_schedule()? {
+            Schedule::Finite(count, _)
+            | Schedule::FiniteOnOff(count, _, _)
+            | Schedule::FinitePattern(count, _) => Some(count.saturating_add(1)),
+            Schedule::Blinks(count, _, _) => Some(*count),
+            Schedule::FadeIn(_) | Schedule::FadeOut(_) => Some(1),
+            _ => None,
+        }
+    }
+    /// Returns the current logical level of the output, if known.
+    pub fn level(&self) -> Option<Level> {
+        self.level
+    }
+    /// Sets the current logical level of the output, e.g. the level it starts at.
+    /// Toggling schedules start from low when the level is unknown.
+    pub fn set_level(&mut self, level: Level) {
+        self.level = Some(level);
+    }
+    /// Returns how long the schedules last in total, or `None` if any of them is infinite,
+    /// or if the total is too long to be represented.
+    /// It is computed from the counts, so it is cheap even for schedules repeating many times.
+    pub fn total_duration(&self) -> Option<Duration> {
+        self.stack
+            .iter()
+            .try_fold(0u64, |total, entry| {
+                total.checked_add(entry.remaining_ticks()?)
+            })
+            .map(Duration::from_ticks)
+    }
+
+    /// Returns how the next step drives the output, and how long it lasts.
+    pub(crate) fn current_step(&self) -> Option<(Drive, Duration)> {
+        self.stack.last().and_then(Entry::next_step)
+    }
+
+    /// Returns the instant the schedule on the top of the stack expires at, if any.
+    pub(crate) fn top_expiry(&self) -> Option<Instant> {
+        self.stack.last().and_then(|entry| entry.expires_at)
+    }
+
+    /// Records the level read from the output for the interrupted schedules that are waiting for it.
+    pub(crate) fn capture(&mut self, level: Level) {
+        self.level = Some(level);
+        self.capture_pending = false;
+        for entry in self.stack.iter_mut().rev().skip(1) {
+            entry
+                .resume_level
+                .get_or_insert(level.untoggled(entry.abandoned_toggle));
+        }
+    }
+
+    /// Counts the current step as done, popping the schedule on the top of the stack once it is over.
+    /// When it is popped, returns its identifier,
+    /// and the level recorded for the schedule below if the output has to be driven back to it.
+    pub(crate) fn advance(&mut self) -> Option<(ScheduleId, Option<Level>)> {
+        let entry = self.stack.last_mut()?;
+        let id = entry.id;
+        let mut should_pop = false;
+        entry.cursor += 1;
+        let wrapped = entry.cursor >= entry.schedule.cycle_len();
+        if wrapped {
+            entry.cursor = 0;
+        }
+        let count = match &mut entry.schedule {
+            Schedule::Finite(count, _) | Schedule::FiniteOnOff(count, _, _) => Some(count),
+            Schedule::FinitePattern(count, _) if wrapped => Some(count),
+            Schedule::Blinks(count, _, _) => {
+                if wrapped {
+                    *count = count.saturating_sub(1);
+                }
+                should_pop = *count == 0;
+                None
+            }
+            Schedule::FadeIn(_) | Schedule::FadeOut(_) => {
+                should_pop = wrapped;
+                None
+            }
+            _ => None,
+        };
+        if let Some(count) = count {
+            if let Some(c) = count.checked_sub(1) {
+                *count = c;
+            } else {
+                should_pop = true;
+            }
+        }
+        if !should_pop {
+            return None;
+        }
+        self.stack.pop();
+        Some((id, self.resume_level()))
+    }
+
+    /// Takes the level recorded for the schedule on the top of the stack, if it differs from the current one.
+    fn resume_level(&mut self) -> Option<Level> {
+        let level = self
+            .stack
+            .last_mut()
+            .and_then(|entry| entry.resume_level.take())?;
+        (self.level != Some(level)).then_some(level)
+    }
+}
+
+impl<const N: usize> Default for Timeline<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Iterator for Timeline<N> {
+    type Item = Edge;
+
+    /// Executes one step of the schedule on the top of the stack, and returns the edge it produces.
+    /// Steps that do not drive the output(e.g. `Schedule::Blinks` with a count of zero) are skipped.
+    /// Returns `None` when the stack becomes empty, or when the schedule on the top never drives the output.
+    fn next(&mut self) -> Option<Edge> {
+        if let Some(level) = self.restore.take() {
+            self.level = Some(level);
+        }
+        let (drive, duration) = loop {
+            let entry = self.stack.last()?;
+            if let Some(step) = entry.next_step() {
+                break step;
+            }
+            if entry.schedule.is_infinite() {
+                return None;
+            }
+            if let Some((_, Some(level))) = self.advance() {
+                self.level = Some(level);
+            }
+        };
+        let level = match drive {
+            Drive::Toggle => self.level.unwrap_or(Level::OFF).toggled(),
+            Drive::Set(level) => level,
+        };
+        self.level = Some(level);
+        if let Some((_, Some(level))) = self.advance() {
+            self.level = Some(level);
+        }
+        Some(Edge { level, duration })
+    }
+}
+
+/// A schedule on the stack of a [`Timeline`], with the state needed to resume it.
+#[derive(Clone)]
+pub(crate) struct Entry {
+    id: ScheduleId,
+    /// Used to choose which schedule to drop with `Overflow::DropLowerPriority`, and to order conditions.
+    priority: u8,
+    /// Name of the condition the schedule displays, if it was asserted with `Timeline::assert_condition`.
+    condition: Option<&'static str>,
+    /// Instant the schedule is removed at, wherever it is on the stack.
+    expires_at: Option<Instant>,
+    pub(crate) schedule: Schedule,
+    /// Index of the step of the schedule's cycle that is executed next.
+    pub(crate) cursor: usize,
+    /// Level of the output when the schedule was interrupted by another one.
+    resume_level: Option<Level>,
+    /// Whether the schedule was interrupted in the middle of a toggling step.
+    abandoned_toggle: bool,
+}
+
+impl Entry {
+    fn new(id: ScheduleId, priority: u8, schedule: Schedule) -> Self {
+        Self {
+            id,
+            priority,
+            condition: None,
+            expires_at: None,
+            schedule,
+            cursor: 0,
+            resume_level: None,
+            abandoned_toggle: false,
+        }
+    }
+
+    /// Returns how many ticks the rest of the schedule lasts, including the next step,
+    /// or `None` if it is infinite or if it overflows.
+    fn remaining_ticks(&self) -> Option<u64> {
+        let sum = |segments: &[Segment]| {
+            segments.iter().try_fold(0u64, |total, segment| {
+                total.checked_add(segment.duration.as_ticks())
+            })
+        };
+        match &self.schedule {
+            Schedule::Finite(count, dur) => dur.as_ticks().checked_mul(u64::from(*count) + 1),
+            Schedule::FiniteOnOff(count, on, off) => {
+                let steps = u64::from(*count) + 1;
+                let (first, second) = if self.cursor == 0 {
+                    (on, off)
+                } else {
+                    (off, on)
+                };
+                let first = first.as_ticks().checked_mul(steps - steps / 2)?;
+                first.checked_add(second.as_ticks().checked_mul(steps / 2)?)
+            }
+            Schedule::FinitePattern(count, pattern) => {
+                let rest = sum(pattern.get(self.cursor..).unwrap_or_default())?;
+                let passes = sum(pattern)?.checked_mul(u64::from(*count))?;
+                passes.checked_add(rest)
+            }
+            Schedule::Blinks(0, _, _) => Some(0),
+            Schedule::Blinks(count, on, off) => {
+                let blink = on.as_ticks().checked_add(off.as_ticks())?;
+                let rest = blink.checked_mul(u64::from(*count) - 1)?;
+                if self.cursor == 0 {
+                    rest.checked_add(blink)
+                } else {
+                    rest.checked_add(off.as_ticks())
+                }
+            }
+            Schedule::FadeIn(fade) | Schedule::FadeOut(fade) => {
+                Some(fade.remaining_duration(self.cursor).as_ticks())
+            }
+            Schedule::Infinite(..)
+            | Schedule::InfiniteOnOff(..)
+            | Schedule::InfinitePattern(..)
+            | Schedule::Breathe(..) => None,
+        }
+    }
+
+    /// Returns how the next step drives the output, and how long it lasts.
+    fn next_step(&self) -> Option<(Drive, Duration)> {
+        match &self.schedule {
+            Schedule::Finite(_, dur) | Schedule::Infinite(dur) => Some((Drive::Toggle, *dur)),
+            Schedule::Blinks(0, _, _) => None,
+            Schedule::FiniteOnOff(_, on, off)
+            | Schedule::InfiniteOnOff(on, off)
+            | Schedule::Blinks(_, on, off) => {
+                if self.cursor == 0 {
+                    Some((Drive::Set(Level::ON), *on))
+                } else {
+                    Some((Drive::Set(Level::OFF), *off))
+                }
+            }
+            Schedule::FinitePattern(_, pattern) | Schedule::InfinitePattern(pattern) => pattern
+                .get(self.cursor)
+                .map(|segment| (Drive::Set(segment.level), segment.duration)),
+            Schedule::Breathe(fade) => Some(fade.breathe_step(self.cursor)),
+            Schedule::FadeIn(fade) => Some(fade.fade_in_step(self.cursor)),
+            Schedule::FadeOut(fade) => Some(fade.fade_out_step(self.cursor)),
+        }
+    }
+}
+
+/// How a step drives the output.
+#[derive(Clone, Copy)]
+pub(crate) enum Drive {
+    Toggle,
+    Set(Level),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Fade, Pattern};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_timeline_blinks_edges() {
+        let mut timeline = Timeline::<2>::new();
+        let _ = timeline.push_schedule(Schedule::Blinks(2, ms(100), ms(300)));
+
+        let edges: std::vec::Vec<_> = timeline.clone().collect();
+        assert_eq!(
+            edges,
+            [
+                Edge {
+                    level: Level::ON,
+                    duration: ms(100)
+                },
+                Edge {
+                    level: Level::OFF,
+                    duration: ms(300)
+                },
+                Edge {
+                    level: Level::ON,
+                    duration: ms(100)
+                },
+                Edge {
+                    level: Level::OFF,
+                    duration: ms(300)
+                },
+            ]
+        );
+        // クローンを回しても元のタイムラインは変わらないはず
+        assert_eq!(timeline.len(), 1);
+        assert_eq!(timeline.remaining_count(), Some(2));
+        assert_eq!(timeline.total_duration(), Some(ms(800)));
+
+        timeline.nth(2);
+        // 2回目の点滅の途中
+        assert_eq!(timeline.remaining_count(), Some(1));
+        assert_eq!(timeline.level(), Some(Level::ON));
+    }
+
+    #[test]
+    fn test_timeline_toggle_edges() {
+        let mut timeline = Timeline::<2>::new();
+        timeline.set_level(Level::ON);
+        let _ = timeline.push_schedule(Schedule::Finite(2, ms(10)));
+
+        let levels: std::vec::Vec<_> = timeline.map(|edge| edge.level).collect();
+        assert_eq!(levels, [Level::OFF, Level::ON, Level::OFF]);
+    }
+
+    #[test]
+    fn test_timeline_restores_level() {
+        let mut timeline = Timeline::<2>::new();
+        timeline.set_level(Level::OFF);
+        let _ = timeline.push_schedule(Schedule::Infinite(ms(10)));
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::ON));
+
+        const PATTERN: Pattern = &[Segment::low(Duration::from_millis(20))];
+        let _ = timeline.push_schedule(Schedule::FinitePattern(0, PATTERN));
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::OFF));
+        // 割り込み前のレベル(High)に戻ってからトグルするはず
+        assert_eq!(timeline.level(), Some(Level::ON));
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::OFF));
+        assert_eq!(timeline.total_duration(), None);
+    }
+
+    #[test]
+    fn test_timeline_skips_empty_steps() {
+        let mut timeline = Timeline::<2>::new();
+        let _ = timeline.push_schedule(Schedule::Finite(0, ms(10)));
+        let _ = timeline.push_schedule(Schedule::Blinks(0, ms(10), ms(10)));
+
+        assert_eq!(timeline.total_duration(), Some(ms(10)));
+        assert_eq!(timeline.count(), 1);
+
+        let mut timeline = Timeline::<1>::new();
+        let _ = timeline.push_schedule(Schedule::InfinitePattern(&[]));
+        assert_eq!(timeline.next(), None);
+    }
+
+    #[test]
+    fn test_timeline_total_duration() {
+        const PATTERN: Pattern = &[
+            Segment::high(Duration::from_millis(10)),
+            Segment::low(Duration::from_millis(30)),
+        ];
+        let fade = Fade {
+            steps: 3,
+            ..Fade::new(ms(100), 0, 255)
+        };
+        let mut timeline = Timeline::<4>::new();
+        // 途中まで進めたスケジュールを積み重ねる
+        for schedule in [
+            Schedule::FinitePattern(1, PATTERN),
+            Schedule::FiniteOnOff(2, ms(10), ms(30)),
+            Schedule::Blinks(2, ms(10), ms(20)),
+            Schedule::FadeIn(fade),
+        ] {
+            let _ = timeline.push_schedule(schedule);
+            timeline.next();
+        }
+
+        // 計算した長さは、実際に回した長さと一致するはず
+        while !timeline.is_empty() {
+            let total = timeline
+                .clone()
+                .fold(Duration::from_ticks(0), |total, edge| total + edge.duration);
+            assert_eq!(timeline.total_duration(), Some(total));
+            timeline.next();
+        }
+
+        // 回数が多くても一瞬で計算でき、溢れるならNoneになるはず
+        let _ = timeline.push_schedule(Schedule::Blinks(u32::MAX, ms(1), ms(1)));
+        assert_eq!(timeline.total_duration(), Some(ms(2 * u64::from(u32::MAX))));
+        let _ = timeline.push_schedule(Schedule::Finite(
+            u32::MAX,
+            Duration::from_ticks(u64::MAX / 2),
+        ));
+        assert_eq!(timeline.total_duration(), None);
+    }
+
+    #[test]
+    fn test_timeline_remove_by_id() {
+        let mut timeline = Timeline::<3>::new();
+        timeline.set_level(Level::OFF);
+        let base = timeline.push_schedule(Schedule::Infinite(ms(10))).unwrap();
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::ON));
+        let middle = timeline
+            .push_schedule(Schedule::InfiniteOnOff(ms(20), ms(20)))
+            .unwrap();
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::ON));
+        let top = timeline
+            .push_schedule(Schedule::InfiniteOnOff(ms(30), ms(30)))
+            .unwrap();
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::ON));
+
+        // 途中のスケジュールだけを取り除く
+        assert_eq!(
+            timeline.remove(middle),
+            Some(Schedule::InfiniteOnOff(ms(20), ms(20)))
+        );
+        assert_eq!(timeline.remove(middle), None);
+        assert_eq!(timeline.peek().map(|(id, _)| id), Some(top));
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::OFF));
+
+        // 一番上を取り除くと、一番下が中断されたときのレベル(High)に戻ってから再開するはず
+        assert_eq!(
+            timeline.remove(top),
+            Some(Schedule::InfiniteOnOff(ms(30), ms(30)))
+        );
+        assert_eq!(timeline.peek().map(|(id, _)| id), Some(base));
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::OFF));
+        assert_eq!(timeline.level(), Some(Level::OFF));
+    }
+
+    #[test]
+    fn test_timeline_drop_lower_priority() {
+        let mut timeline = Timeline::<3>::new();
+        let overflow = Overflow::DropLowerPriority;
+        let low = timeline.push_with_priority(Schedule::Infinite(ms(10)), 1, overflow);
+        let high = timeline.push_with_priority(Schedule::Infinite(ms(20)), 3, overflow);
+        let lowest = timeline.push_with_priority(Schedule::Infinite(ms(30)), 0, overflow);
+
+        // 満杯のときは一番優先度の低いスケジュールが捨てられるはず
+        let urgent = timeline
+            .push_with_priority(Schedule::Infinite(ms(40)), 2, overflow)
+            .unwrap();
+        assert_eq!(timeline.len(), 3);
+        assert_eq!(timeline.remove(lowest.unwrap()), None);
+
+        let _ = timeline.push_with_priority(Schedule::Infinite(ms(50)), 2, overflow);
+        assert_eq!(timeline.remove(low.unwrap()), None);
+        // 優先度の低いスケジュールがなければ、追加できないはず
+        assert_eq!(
+            timeline.push_with_priority(Schedule::Infinite(ms(60)), 2, overflow),
+            Err(Schedule::Infinite(ms(60)))
+        );
+        assert!(timeline.remove(high.unwrap()).is_some());
+        assert!(timeline.remove(urgent).is_some());
+    }
+
+    #[test]
+    fn test_timeline_enqueue() {
+        let mut timeline = Timeline::<4>::new();
+        let overflow = Overflow::Reject;
+        let _ = timeline.enqueue(Schedule::Infinite(ms(10)), 0, overflow);
+        let _ = timeline.enqueue(Schedule::Blinks(1, ms(20), ms(20)), 0, overflow);
+        let _ = timeline.enqueue(Schedule::Blinks(1, ms(30), ms(30)), 0, overflow);
+        let _ = timeline.enqueue(Schedule::Infinite(ms(40)), 0, overflow);
+
+        // 有限のスケジュールが追加順に実行され、最後に追加された無限のスケジュールに戻るはず
+        let durations: std::vec::Vec<_> = timeline.take(6).map(|edge| edge.duration).collect();
+        assert_eq!(durations, [ms(20), ms(20), ms(30), ms(30), ms(40), ms(40)]);
+    }
+
+    #[test]
+    fn test_timeline_enqueue_evict_oldest() {
+        let mut timeline = Timeline::<3>::new();
+        let overflow = Overflow::EvictOldest;
+        let background = timeline.enqueue(Schedule::Infinite(ms(10)), 0, overflow);
+        let first = timeline.enqueue(Schedule::Blinks(1, ms(20), ms(20)), 0, overflow);
+        let _ = timeline.enqueue(Schedule::Blinks(1, ms(30), ms(30)), 0, overflow);
+        let _ = timeline.enqueue(Schedule::Blinks(1, ms(40), ms(40)), 0, overflow);
+
+        // 満杯のときは背景ではなく、最初にキューに入れた有限のスケジュールが捨てられるはず
+        assert!(!timeline.contains(first.unwrap()));
+        assert!(timeline.contains(background.unwrap()));
+        let durations: std::vec::Vec<_> = timeline.take(6).map(|edge| edge.duration).collect();
+        assert_eq!(durations, [ms(30), ms(30), ms(40), ms(40), ms(10), ms(10)]);
+    }
+
+    #[test]
+    fn test_timeline_conditions() {
+        let mut timeline = Timeline::<4>::new();
+        let overflow = Overflow::Reject;
+        let charging = Schedule::Infinite(ms(500));
+        let fault = Schedule::InfiniteOnOff(ms(50), ms(50));
+        let pairing = Schedule::Infinite(ms(100));
+        let _ = timeline.assert_condition("charging", 1, charging.clone(), overflow);
+        let _ = timeline.assert_condition("fault", 3, fault.clone(), overflow);
+        // 優先度の低い条件は、高い条件の下に入るはず
+        let _ = timeline.assert_condition("pairing", 2, pairing.clone(), overflow);
+        assert_eq!(timeline.active_schedule(), Some(&fault));
+
+        // 一番高い条件を解除すると、次に高い条件に戻るはず
+        assert_eq!(timeline.deassert_condition("fault"), Some(fault));
+        assert_eq!(timeline.active_schedule(), Some(&pairing));
+        assert!(!timeline.is_asserted("fault"));
+
+        // 優先度を上げ直すと並び替えられるはず
+        let _ = timeline.assert_condition("charging", 3, charging.clone(), overflow);
+        assert_eq!(timeline.active_schedule(), Some(&charging));
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(timeline.deassert_condition("unknown"), None);
+    }
+
+    #[test]
+    fn test_timeline_replace_condition() {
+        let mut timeline = Timeline::<2>::new();
+        let _ = timeline.assert_condition("fault", 3, Schedule::Infinite(ms(50)), Overflow::Reject);
+        let id = timeline.replace_top(Schedule::Infinite(ms(10))).unwrap();
+
+        // 置き換えたスケジュールは条件を表示していないので、解除しても残るはず
+        assert!(!timeline.is_asserted("fault"));
+        assert_eq!(timeline.deassert_condition("fault"), None);
+        assert!(timeline.contains(id));
+    }
+
+    #[test]
+    fn test_timeline_replace_expiring() {
+        let mut timeline = Timeline::<2>::new();
+        let id = timeline.push_schedule(Schedule::Infinite(ms(10))).unwrap();
+        assert!(timeline.set_expiry(id, Instant::from_millis(100)));
+        let replaced = timeline.replace_top(Schedule::Infinite(ms(20))).unwrap();
+
+        // 置き換えたスケジュールは期限を引き継がないはず
+        assert!(!timeline.expire(Instant::from_millis(100)));
+        assert!(timeline.contains(replaced));
+    }
+
+    #[test]
+    fn test_timeline_expire() {
+        let mut timeline = Timeline::<3>::new();
+        let start = Instant::from_millis(1000);
+        timeline.set_level(Level::OFF);
+        let _ = timeline.push_schedule(Schedule::Infinite(ms(10)));
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::ON));
+        let middle = timeline.push_schedule(Schedule::Infinite(ms(20))).unwrap();
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::OFF));
+        let top = timeline.push_schedule(Schedule::Infinite(ms(30))).unwrap();
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::ON));
+        assert!(timeline.set_expiry(middle, start + ms(50)));
+        assert!(timeline.set_expiry(top, start + ms(100)));
+
+        assert!(!timeline.expire(start + ms(49)));
+        assert!(timeline.expire(start + ms(50)));
+        assert_eq!(timeline.len(), 2);
+        // 一度に複数のスケジュールが期限切れになると、一番下のスケジュールのレベルに戻るはず
+        assert!(timeline.expire(start + ms(100)));
+        assert!(!timeline.set_expiry(top, start));
+        assert_eq!(timeline.next().map(|edge| edge.level), Some(Level::OFF));
+    }
+}
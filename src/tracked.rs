@@ -0,0 +1,122 @@
+//! Using a plain `OutputPin` with a [`Blinker`](crate::Blinker).
+use embedded_hal::digital::{ErrorType, OutputPin, PinState, StatefulOutputPin};
+
+/// An output pin whose level is tracked in software,
+/// so that a plain [`OutputPin`](e.g. of a port expander) can be used where a [`StatefulOutputPin`] is required.
+/// Toggling is done with `set_high`/`set_low`.
+pub struct TrackedPin<P: OutputPin> {
+    pin: P,
+    level: PinState,
+}
+
+impl<P: OutputPin> TrackedPin<P> {
+    /// Create a new `TrackedPin` struct.
+    /// `level` is the level the pin is currently at.
+    pub fn new(pin: P, level: PinState) -> Self {
+        Self { pin, level }
+    }
+    /// Returns the wrapped pin.
+    pub fn into_inner(self) -> P {
+        self.pin
+    }
+}
+
+impl<P: OutputPin> ErrorType for TrackedPin<P> {
+    type Error = P::Error;
+}
+
+impl<P: OutputPin> OutputPin for TrackedPin<P> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_low()?;
+        self.level = PinState::Low;
+        Ok(())
+    }
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_high()?;
+        self.level = PinState::High;
+        Ok(())
+    }
+}
+
+impl<P: OutputPin> StatefulOutputPin for TrackedPin<P> {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.level == PinState::High)
+    }
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.level == PinState::Low)
+    }
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        self.set_state(!self.level)
+    }
+}
+
+#[cfg(feature = "embassy")]
+impl<P: OutputPin, const N: usize> crate::Blinker<TrackedPin<P>, N> {
+    /// Create a new `Blinker` struct over a plain [`OutputPin`], tracking its level in software(see [`TrackedPin`]).
+    /// `level` is the level the pin is currently at.
+    pub fn with_output_pin(pin: P, level: PinState) -> Self {
+        Self::new(TrackedPin::new(pin, level))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Blinker, Schedule};
+    use embassy_futures::block_on;
+    use embassy_time::Duration;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction};
+
+    #[test]
+    fn test_tracked_pin_toggle() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::with_output_pin(&mut pin, PinState::Low);
+
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(10)));
+
+        block_on(async {
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+        });
+
+        drop(blinker);
+        pin.done();
+    }
+
+    #[test]
+    fn test_tracked_pin_restore_level() {
+        let expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            // ピンを読まずに元の状態に戻すはず
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+        ];
+        let mut pin = PinMock::new(&expectations);
+        let mut blinker = Blinker::<_, 2>::with_output_pin(&mut pin, PinState::Low);
+
+        let _ = blinker.push_schedule(Schedule::Infinite(Duration::from_millis(10)));
+
+        block_on(async {
+            blinker.step().await.expect("infallible");
+            let _ = blinker.push_schedule(Schedule::Blinks(
+                1,
+                Duration::from_millis(10),
+                Duration::from_millis(10),
+            ));
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+            blinker.step().await.expect("infallible");
+        });
+
+        drop(blinker);
+        pin.done();
+    }
+}